@@ -0,0 +1,166 @@
+// Copyright (C) 2022 The brightness project authors. Distributed under the 0BSD license.
+
+//! Backlight types and duplicate detection.
+
+use std::path::PathBuf;
+
+/// How a backlight is controlled, as reported by the device.
+///
+/// When several devices control the same panel, firmware devices are preferred over platform
+/// devices, which are preferred over raw devices.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum DeviceType {
+    /// Controlled through a standard firmware interface (e.g. ACPI).
+    Firmware,
+    /// Controlled through a platform-specific interface (e.g. the embedded controller).
+    Platform,
+    /// Controlled directly through the hardware registers (e.g. of the graphics card).
+    Raw,
+    /// The device does not report its type.
+    #[default]
+    Unknown,
+}
+
+impl DeviceType {
+    fn preference(self) -> u8 {
+        match self {
+            DeviceType::Firmware => 0,
+            DeviceType::Platform => 1,
+            DeviceType::Raw => 2,
+            DeviceType::Unknown => 3,
+        }
+    }
+}
+
+/// Closest PCI or platform device a backlight belongs to.
+// Only Linux reports the parents of backlights.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) enum Parent {
+    Pci { path: PathBuf, display: bool },
+    Platform,
+}
+
+/// Sorts devices by type preference and removes raw devices that duplicate a firmware or platform
+/// device, following the rules of systemd-backlight.
+///
+/// A raw device belonging to a display controller is considered a duplicate if a firmware or
+/// platform device belongs to the same display controller or to the platform itself.
+pub(crate) fn dedup<T, F>(devices: Vec<T>, info: F) -> Vec<T>
+where
+    F: Fn(&T) -> (DeviceType, Option<Parent>),
+{
+    let mut devices = devices
+        .into_iter()
+        .map(|d| {
+            let (ty, parent) = info(&d);
+            (d, ty, parent)
+        })
+        .collect::<Vec<_>>();
+    let preferred = devices
+        .iter()
+        .filter(|(_, ty, _)| matches!(ty, DeviceType::Firmware | DeviceType::Platform))
+        .filter_map(|(_, _, parent)| parent.clone())
+        .collect::<Vec<_>>();
+    devices.retain(|(_, ty, parent)| match (ty, parent) {
+        (
+            DeviceType::Raw,
+            Some(Parent::Pci {
+                path,
+                display: true,
+            }),
+        ) => !preferred.iter().any(|p| match p {
+            Parent::Pci { path: other, .. } => other == path,
+            Parent::Platform => true,
+        }),
+        _ => true,
+    });
+    devices.sort_by_key(|(_, ty, _)| ty.preference());
+    devices.into_iter().map(|(d, _, _)| d).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gpu(display: bool) -> Option<Parent> {
+        Some(Parent::Pci {
+            path: "/sys/devices/pci0000:00/0000:00:02.0".into(),
+            display,
+        })
+    }
+
+    fn other_gpu() -> Option<Parent> {
+        Some(Parent::Pci {
+            path: "/sys/devices/pci0000:00/0000:00:01.0/0000:01:00.0".into(),
+            display: true,
+        })
+    }
+
+    fn names(devices: &[(&'static str, DeviceType, Option<Parent>)]) -> Vec<&'static str> {
+        dedup(devices.to_vec(), |(_, ty, parent)| (*ty, parent.clone()))
+            .into_iter()
+            .map(|(name, ..)| name)
+            .collect()
+    }
+
+    #[test]
+    fn raw_devices_duplicating_firmware_or_platform_devices_are_removed() {
+        let devices = [
+            ("intel_backlight", DeviceType::Raw, gpu(true)),
+            ("acpi_video0", DeviceType::Firmware, gpu(true)),
+        ];
+        assert_eq!(names(&devices), ["acpi_video0"]);
+
+        // Platform devices control the panel whatever its graphics card.
+        let devices = [
+            ("amdgpu_bl0", DeviceType::Raw, other_gpu()),
+            ("nv_backlight", DeviceType::Platform, Some(Parent::Platform)),
+        ];
+        assert_eq!(names(&devices), ["nv_backlight"]);
+    }
+
+    #[test]
+    fn devices_are_sorted_by_type_preference() {
+        let devices = [
+            ("amdgpu_bl0", DeviceType::Raw, other_gpu()),
+            ("dell_uart_backlight", DeviceType::Platform, gpu(true)),
+            ("ddcci5", DeviceType::Unknown, None),
+            ("acpi_video0", DeviceType::Firmware, gpu(true)),
+        ];
+        assert_eq!(
+            names(&devices),
+            ["acpi_video0", "dell_uart_backlight", "amdgpu_bl0", "ddcci5"]
+        );
+    }
+
+    #[test]
+    fn devices_without_shared_display_controller_are_kept() {
+        let devices = [
+            ("intel_backlight", DeviceType::Raw, None),
+            ("acpi_video0", DeviceType::Firmware, None),
+            ("radeon_bl0", DeviceType::Raw, gpu(false)),
+            ("acpi_video1", DeviceType::Firmware, gpu(false)),
+        ];
+        assert_eq!(
+            names(&devices),
+            [
+                "acpi_video0",
+                "acpi_video1",
+                "intel_backlight",
+                "radeon_bl0"
+            ]
+        );
+    }
+
+    #[test]
+    fn laptop_panel_is_preferred_over_external_monitor() {
+        let devices = [
+            ("ddcci7", DeviceType::Unknown, None),
+            ("intel_backlight", DeviceType::Raw, gpu(true)),
+            ("acpi_video0", DeviceType::Firmware, gpu(true)),
+        ];
+        assert_eq!(names(&devices).first(), Some(&"acpi_video0"));
+        assert_eq!(names(&devices[..2]), ["intel_backlight", "ddcci7"]);
+    }
+}
@@ -0,0 +1,172 @@
+// Copyright (C) 2022 The brightness project authors. Distributed under the 0BSD license.
+
+//! Operations run on all devices at once.
+
+use crate::{Error, blocking};
+use itertools::Itertools;
+use std::{panic, thread};
+
+/// Results of an operation run on all devices, e.g. by [`set_all`](blocking::set_all) and
+/// [`get_all`](blocking::get_all).
+///
+/// A failure on one device doesn't prevent the operation from running on the others.
+#[derive(Debug)]
+pub struct BatchReport<D, T> {
+    /// Devices found, with the result of the operation on each, in enumeration order.
+    pub results: Vec<(D, Result<T, Error>)>,
+    /// Errors that prevented finding some devices, which the operation didn't run on.
+    pub listing_errors: Vec<Error>,
+}
+
+impl<D, T> BatchReport<D, T> {
+    /// Returns whether all devices were found and the operation succeeded on all of them.
+    pub fn is_ok(&self) -> bool {
+        self.errors().next().is_none()
+    }
+
+    /// Returns an iterator over all errors, listing errors first.
+    pub fn errors(&self) -> impl Iterator<Item = &Error> {
+        self.listing_errors
+            .iter()
+            .chain(self.results.iter().filter_map(|(_, r)| r.as_ref().err()))
+    }
+}
+
+/// Splits enumerated devices from the errors that occurred while listing them.
+pub(crate) fn partition<D>(
+    devices: impl IntoIterator<Item = Result<D, Error>>,
+) -> (Vec<D>, Vec<Error>) {
+    devices.into_iter().partition_result()
+}
+
+/// Runs `op` on each device in its own thread.
+pub(crate) fn run<T, F>(
+    devices: impl IntoIterator<Item = Result<blocking::BrightnessDevice, Error>>,
+    op: F,
+) -> BatchReport<blocking::BrightnessDevice, T>
+where
+    T: Send,
+    F: Fn(&blocking::BrightnessDevice) -> Result<T, Error> + Sync,
+{
+    let (devices, listing_errors) = partition(devices);
+    let results = thread::scope(|s| {
+        let threads = devices
+            .iter()
+            .map(|d| s.spawn(|| op(d)))
+            .collect::<Vec<_>>();
+        threads
+            .into_iter()
+            .map(|t| t.join().unwrap_or_else(|e| panic::resume_unwind(e)))
+            .collect::<Vec<_>>()
+    });
+    BatchReport {
+        results: devices.into_iter().zip(results).collect(),
+        listing_errors,
+    }
+}
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use super::*;
+    use crate::{blocking::Brightness, mock::MockBackend};
+
+    /// Returns a backend with three devices, the second of which fails.
+    fn backend() -> MockBackend {
+        let backend = MockBackend::new()
+            .with_device("first", 100)
+            .with_device("failing", 100)
+            .with_device("last", 200);
+        backend.fail_getting("failing", true);
+        backend.fail_setting("failing", true);
+        backend
+    }
+
+    /// Returns the device names with the results of a report.
+    fn names<D, T: Clone>(
+        report: &BatchReport<D, T>,
+        name: impl Fn(&D) -> String,
+    ) -> Vec<(String, Option<T>)> {
+        report
+            .results
+            .iter()
+            .map(|(d, r)| (name(d), r.as_ref().ok().cloned()))
+            .collect()
+    }
+
+    #[test]
+    fn other_devices_are_set_when_one_fails() {
+        let backend = backend();
+        let report = run(backend.blocking_brightness_devices(), |d| d.set(40));
+        assert_eq!(
+            (backend.value("first"), backend.value("last")),
+            (Some(40), Some(80))
+        );
+        assert_eq!(backend.value("failing"), Some(100));
+        let results = names(&report, |d| d.device_name().unwrap());
+        let expected = [("first", Some(())), ("failing", None), ("last", Some(()))];
+        assert_eq!(results, expected.map(|(n, r)| (n.to_owned(), r)));
+        assert!(matches!(
+            &report.results[1].1,
+            Err(Error::SettingBrightness { device, .. }) if device == "failing"
+        ));
+        assert!(!report.is_ok());
+        assert_eq!(report.errors().count(), 1);
+    }
+
+    #[test]
+    fn other_devices_are_read_when_one_fails() {
+        let backend = backend();
+        backend.set_value("last", 50);
+        let report = run(backend.blocking_brightness_devices(), Brightness::get);
+        let results = names(&report, |d| d.device_name().unwrap());
+        let expected = [("first", Some(100)), ("failing", None), ("last", Some(25))];
+        assert_eq!(results, expected.map(|(n, r)| (n.to_owned(), r)));
+        assert!(matches!(
+            &report.results[1].1,
+            Err(Error::GettingDeviceInfo { device, .. }) if device == "failing"
+        ));
+    }
+
+    #[test]
+    fn listing_errors_are_reported() {
+        let backend = backend();
+        backend.fail_listing(true);
+        let report = run(backend.blocking_brightness_devices(), Brightness::get);
+        assert!(report.results.is_empty());
+        assert!(matches!(
+            report.listing_errors[..],
+            [Error::ListingDevices(_)]
+        ));
+        assert!(!report.is_ok());
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn async_batches_report_failures_per_device() {
+        use crate::r#async::{get_all_of, set_all_of};
+        use futures::executor::block_on;
+
+        let backend = backend();
+        let report = block_on(set_all_of(backend.brightness_devices(), 40));
+        assert_eq!(
+            (backend.value("first"), backend.value("last")),
+            (Some(40), Some(80))
+        );
+        assert!(matches!(
+            &report.results[1].1,
+            Err(Error::SettingBrightness { device, .. }) if device == "failing"
+        ));
+        assert_eq!(report.errors().count(), 1);
+
+        let report = block_on(get_all_of(backend.brightness_devices()));
+        let results = names(&report, |d| {
+            block_on(crate::Brightness::device_name(d)).unwrap()
+        });
+        let expected = [("first", Some(40)), ("failing", None), ("last", Some(40))];
+        assert_eq!(results, expected.map(|(n, r)| (n.to_owned(), r)));
+        assert!(matches!(
+            &report.results[1].1,
+            Err(Error::GettingDeviceInfo { device, .. }) if device == "failing"
+        ));
+    }
+}
@@ -2,11 +2,25 @@
 
 //! The blocking API.
 
-use crate::Error;
+use crate::{
+    BatchReport, Capabilities, ColorPreset, DeviceId, DeviceType, DisplayInfo, Easing, Error,
+    InputSource, Level, Perception, PowerMode, Scale, VcpValue, backlight, batch,
+    conversion::{STEP, adjusted_raw},
+    floor,
+    transition::Steps,
+    vcp,
+    watch::Watcher,
+};
+use itertools::Either;
+use std::{
+    iter::{from_fn, once},
+    thread,
+    time::{Duration, Instant},
+};
 
 cfg_if::cfg_if! {
     if #[cfg(target_os = "linux")] {
-        pub(crate) mod linux;
+        pub mod linux;
         use self::linux as platform;
     } else if #[cfg(windows)] {
         pub mod windows;
@@ -18,7 +32,289 @@ cfg_if::cfg_if! {
 
 /// Blocking brightness device.
 #[derive(Debug)]
-pub struct BrightnessDevice(platform::BlockingDeviceImpl);
+pub struct BrightnessDevice {
+    pub(crate) inner: DeviceImpl,
+    perception: Perception,
+    adjust_bounds: (Level, Level),
+    min_level: Option<Level>,
+}
+
+#[derive(Debug)]
+pub(crate) enum DeviceImpl {
+    Platform(platform::BlockingDeviceImpl),
+    #[cfg(feature = "mock")]
+    Mock(crate::mock::MockDevice),
+}
+
+impl BrightnessDevice {
+    pub(crate) fn new(inner: DeviceImpl) -> Self {
+        Self {
+            inner,
+            perception: Perception::default(),
+            adjust_bounds: (Level::MIN, Level::MAX),
+            min_level: None,
+        }
+    }
+
+    #[cfg(feature = "async")]
+    pub(crate) fn into_parts(self) -> (DeviceImpl, Perception, (Level, Level), Option<Level>) {
+        (
+            self.inner,
+            self.perception,
+            self.adjust_bounds,
+            self.min_level,
+        )
+    }
+
+    /// Returns the mapping applied to percentages and levels.
+    pub fn perception(&self) -> Perception {
+        self.perception
+    }
+
+    /// Sets the mapping applied to percentages and levels. The default is
+    /// [`Perception::Linear`].
+    pub fn set_perception(&mut self, perception: Perception) {
+        self.perception = perception;
+    }
+
+    /// Returns the lowest and highest levels reached by [`adjust`](Brightness::adjust).
+    pub fn adjust_bounds(&self) -> (Level, Level) {
+        self.adjust_bounds
+    }
+
+    /// Sets the lowest and highest levels reached by [`adjust`](Brightness::adjust), on the scale
+    /// given by the [`perception`](Self::perception). The default is the whole range.
+    pub fn set_adjust_bounds(&mut self, min: Level, max: Level) {
+        self.adjust_bounds = if min <= max { (min, max) } else { (max, min) };
+    }
+
+    /// Returns the lowest level written to the device, as a fraction of
+    /// [`max_raw`](Brightness::max_raw) whatever the [`perception`](Self::perception).
+    ///
+    /// Lower values are raised to it by every function setting the brightness. It is the level set
+    /// with [`set_min_level`](Self::set_min_level) if any, otherwise the level set with
+    /// [`set_global_min_level`](crate::set_global_min_level) if any, otherwise 5% for devices of
+    /// type [`DeviceType::Raw`] and [`DeviceType::Platform`] and 0 for other devices.
+    pub fn min_level(&self) -> Level {
+        floor::min_level(self.min_level, self.device_type())
+    }
+
+    /// Sets the lowest level written to the device, or restores the default with `None`.
+    /// [`Level::MIN`] removes the floor.
+    pub fn set_min_level(&mut self, level: Option<Level>) {
+        self.min_level = level;
+    }
+
+    /// Returns the scale reported by the device.
+    pub fn scale(&self) -> Scale {
+        match &self.inner {
+            DeviceImpl::Platform(d) => d.scale(),
+            #[cfg(feature = "mock")]
+            DeviceImpl::Mock(d) => d.scale(),
+        }
+    }
+
+    /// Returns how the backlight is controlled.
+    pub fn device_type(&self) -> DeviceType {
+        match &self.inner {
+            DeviceImpl::Platform(d) => d.device_type(),
+            #[cfg(feature = "mock")]
+            DeviceImpl::Mock(d) => d.device_type(),
+        }
+    }
+
+    /// Returns the name of the connector of the display, e.g. `eDP-1` or `DP-2`, if known.
+    ///
+    /// On Linux, this is the DRM connector the device is matched with, as described in
+    /// [`display_info`](Self::display_info). On Windows, only the connector type is known, e.g.
+    /// `HDMI`.
+    pub fn connector_name(&self) -> Option<String> {
+        match &self.inner {
+            DeviceImpl::Platform(d) => d.connector_name(),
+            #[cfg(feature = "mock")]
+            DeviceImpl::Mock(d) => d.connector_name(),
+        }
+    }
+
+    /// Returns whether the device controls a built-in panel, e.g. of a laptop.
+    ///
+    /// On Linux, backlight devices whose connector is unknown are assumed to, except for monitors
+    /// controlled by the ddcci driver.
+    pub fn is_internal(&self) -> bool {
+        match &self.inner {
+            DeviceImpl::Platform(d) => d.is_internal(),
+            #[cfg(feature = "mock")]
+            DeviceImpl::Mock(d) => d.is_internal(),
+        }
+    }
+
+    /// Returns the identity of the display, decoded from its EDID, or `None` if the display can't
+    /// be identified.
+    ///
+    /// On Linux, the EDID is read from the DRM connector of the display. Backlight devices that
+    /// aren't registered under a connector are matched with the built-in panel of their graphics
+    /// card, if it has only one. On Windows, the EDID is read from the registry.
+    pub fn display_info(&self) -> Result<Option<DisplayInfo>, Error> {
+        let edid = match &self.inner {
+            DeviceImpl::Platform(d) => d.edid()?,
+            #[cfg(feature = "mock")]
+            DeviceImpl::Mock(d) => d.edid()?,
+        };
+        edid.map(|edid| DisplayInfo::from_edid(&edid)).transpose()
+    }
+
+    /// Returns an identifier of the device that stays the same across reboots, see [`DeviceId`].
+    pub fn id(&self) -> DeviceId {
+        let (driver, path) = match &self.inner {
+            DeviceImpl::Platform(d) => (d.driver(), d.stable_path()),
+            #[cfg(feature = "mock")]
+            DeviceImpl::Mock(d) => (d.driver(), d.stable_path()),
+        };
+        // Displays with an invalid EDID are identified by path like unknown displays.
+        let display = (!self.is_internal())
+            .then(|| self.display_info().ok().flatten())
+            .flatten();
+        DeviceId::new(&driver, &path, display)
+    }
+
+    /// Returns an iterator over brightness changes.
+    ///
+    /// The iterator first yields the current brightness as a percentage, then the new brightness
+    /// each time it changes. It never ends, errors included. On Linux, changes are notified by
+    /// sysfs; changes that aren't notified and other platforms are noticed by polling every
+    /// second. Monitors controlled over I2C on Linux can't be watched, as they are too slow to
+    /// poll; for them, a single error is yielded instead.
+    pub fn watch(&self) -> impl Iterator<Item = Result<u32, Error>> + '_ {
+        let mut watcher = match self.watcher() {
+            Ok(w) => w,
+            Err(e) => return Either::Right(once(Err(e))),
+        };
+        let mut last = None;
+        Either::Left(from_fn(move || {
+            loop {
+                if last.is_some()
+                    && let Err(e) = watcher.wait()
+                {
+                    return Some(Err(e));
+                }
+                match self.get() {
+                    Ok(value) if last == Some(value) => {}
+                    Ok(value) => {
+                        last = Some(value);
+                        return Some(Ok(value));
+                    }
+                    Err(e) => return Some(Err(e)),
+                }
+            }
+        }))
+    }
+
+    /// Returns an iterator that gradually changes the brightness to `target` over `duration`.
+    ///
+    /// Each step writes the next raw value and yields it, the last one being the raw value of
+    /// `target`. Dropping the iterator cancels the transition; [`Transition::run`] runs it to
+    /// completion.
+    pub fn transition_to(
+        &self,
+        target: Level,
+        duration: Duration,
+        easing: Easing,
+    ) -> Transition<'_> {
+        Transition {
+            device: self,
+            target,
+            duration,
+            easing,
+            state: None,
+            done: false,
+        }
+    }
+
+    fn steps(&self, target: Level, duration: Duration, easing: Easing) -> Result<Steps, Error> {
+        let max = self.max_raw()?;
+        let current = self.get_level()?;
+        Ok(Steps::new(
+            current,
+            target,
+            max,
+            self.correction(),
+            easing,
+            duration,
+        ))
+    }
+
+    fn watcher(&self) -> Result<Watcher, Error> {
+        Ok(match &self.inner {
+            DeviceImpl::Platform(d) => d.watcher()?,
+            #[cfg(feature = "mock")]
+            DeviceImpl::Mock(d) => d.watcher()?,
+        })
+    }
+
+    fn parent(&self) -> Option<backlight::Parent> {
+        match &self.inner {
+            DeviceImpl::Platform(d) => d.parent(),
+            #[cfg(feature = "mock")]
+            DeviceImpl::Mock(_) => None,
+        }
+    }
+
+    fn correction(&self) -> Option<Perception> {
+        match self.perception {
+            Perception::Linear => None,
+            p => (self.scale() != Scale::NonLinear).then_some(p),
+        }
+    }
+}
+
+/// Gradual brightness change returned by [`BrightnessDevice::transition_to`].
+///
+/// The transition only progresses while iterated and stops after the first error.
+#[derive(Debug)]
+#[must_use = "transitions do nothing unless iterated or run"]
+pub struct Transition<'a> {
+    device: &'a BrightnessDevice,
+    target: Level,
+    duration: Duration,
+    easing: Easing,
+    state: Option<(Instant, Steps)>,
+    done: bool,
+}
+
+impl Transition<'_> {
+    /// Runs the transition to completion.
+    pub fn run(mut self) -> Result<(), Error> {
+        self.try_for_each(|r| r.map(drop))
+    }
+}
+
+impl Iterator for Transition<'_> {
+    type Item = Result<u32, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let (start, steps) = match &mut self.state {
+            Some(state) => state,
+            None => match self.device.steps(self.target, self.duration, self.easing) {
+                Ok(steps) => self.state.insert((Instant::now(), steps)),
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+            },
+        };
+        let Some((offset, raw)) = steps.next() else {
+            self.done = true;
+            return None;
+        };
+        thread::sleep((*start + offset).saturating_duration_since(Instant::now()));
+        let result = self.device.set_raw(raw).map(|()| raw);
+        self.done = result.is_err();
+        Some(result)
+    }
+}
 
 /// Blocking interface to get and set brightness.
 pub trait Brightness {
@@ -28,25 +324,399 @@ pub trait Brightness {
     /// Returns the current brightness as a percentage.
     fn get(&self) -> Result<u32, Error>;
 
-    /// Sets the brightness as a percentage.
+    /// Sets the brightness as a percentage. [`BrightnessDevice`] fails with
+    /// [`Error::LevelOutOfRange`] for percentages above 100.
     fn set(&self, percentage: u32) -> Result<(), Error>;
+
+    /// Returns the current brightness in device-specific units, between 0 and
+    /// [`max_raw`](Brightness::max_raw).
+    fn get_raw(&self) -> Result<u32, Error>;
+
+    /// Returns the maximum brightness in device-specific units.
+    fn max_raw(&self) -> Result<u32, Error>;
+
+    /// Sets the brightness in device-specific units. Values above
+    /// [`max_raw`](Brightness::max_raw) are clamped.
+    fn set_raw(&self, value: u32) -> Result<(), Error>;
+
+    /// Returns the current brightness level.
+    fn get_level(&self) -> Result<Level, Error> {
+        let max = self.max_raw()?;
+        let raw = self.get_raw()?;
+        Ok(Level::from_raw_clamped(raw, max))
+    }
+
+    /// Sets the brightness level.
+    fn set_level(&self, level: Level) -> Result<(), Error> {
+        let max = self.max_raw()?;
+        self.set_raw(level.to_raw(max))
+    }
+
+    /// Changes the brightness by `delta` percentage points.
+    ///
+    /// The new brightness is computed from the current raw value, which changes by at least one
+    /// step unless it is already at the end of the range.
+    fn adjust(&self, delta: i32) -> Result<(), Error> {
+        let max = self.max_raw()?;
+        let raw = self.get_raw()?;
+        let target = adjusted_raw(raw, max, delta, (Level::MIN, Level::MAX), None);
+        if target == raw {
+            return Ok(());
+        }
+        self.set_raw(target)
+    }
+
+    /// Increases the brightness by 5 percentage points, as for a brightness key.
+    fn step_up(&self) -> Result<(), Error> {
+        self.adjust(STEP)
+    }
+
+    /// Decreases the brightness by 5 percentage points, as for a brightness key.
+    fn step_down(&self) -> Result<(), Error> {
+        self.adjust(-STEP)
+    }
 }
 
 impl Brightness for BrightnessDevice {
     fn device_name(&self) -> Result<String, Error> {
-        self.0.device_name()
+        match &self.inner {
+            DeviceImpl::Platform(d) => d.device_name(),
+            #[cfg(feature = "mock")]
+            DeviceImpl::Mock(d) => d.device_name(),
+        }
     }
 
     fn get(&self) -> Result<u32, Error> {
-        self.0.get()
+        if self.correction().is_some() {
+            return Ok(self.get_level()?.to_percentage());
+        }
+        match &self.inner {
+            DeviceImpl::Platform(d) => d.get(),
+            #[cfg(feature = "mock")]
+            DeviceImpl::Mock(d) => d.get(),
+        }
     }
 
     fn set(&self, percentage: u32) -> Result<(), Error> {
-        self.0.set(percentage)
+        let level = Level::from_percentage(percentage)?;
+        if self.correction().is_some() || self.min_level() > Level::MIN {
+            return self.set_level(level);
+        }
+        match &self.inner {
+            DeviceImpl::Platform(d) => d.set(percentage),
+            #[cfg(feature = "mock")]
+            DeviceImpl::Mock(d) => d.set(percentage),
+        }
+    }
+
+    fn get_raw(&self) -> Result<u32, Error> {
+        match &self.inner {
+            DeviceImpl::Platform(d) => d.get_raw(),
+            #[cfg(feature = "mock")]
+            DeviceImpl::Mock(d) => d.get_raw(),
+        }
+    }
+
+    fn max_raw(&self) -> Result<u32, Error> {
+        match &self.inner {
+            DeviceImpl::Platform(d) => d.max_raw(),
+            #[cfg(feature = "mock")]
+            DeviceImpl::Mock(d) => d.max_raw(),
+        }
+    }
+
+    fn set_raw(&self, value: u32) -> Result<(), Error> {
+        let value = match self.min_level() {
+            min if min == Level::MIN => value,
+            min => value.max(floor::min_raw(min, self.max_raw()?)),
+        };
+        match &self.inner {
+            DeviceImpl::Platform(d) => d.set_raw(value),
+            #[cfg(feature = "mock")]
+            DeviceImpl::Mock(d) => d.set_raw(value),
+        }
+    }
+
+    fn get_level(&self) -> Result<Level, Error> {
+        let max = self.max_raw()?;
+        let level = Level::from_raw_clamped(self.get_raw()?, max);
+        Ok(match self.correction() {
+            Some(perception) => perception.perceive(level),
+            None => level,
+        })
+    }
+
+    fn set_level(&self, level: Level) -> Result<(), Error> {
+        let level = match self.correction() {
+            Some(perception) => perception.linearize(level),
+            None => level,
+        };
+        let max = self.max_raw()?;
+        self.set_raw(level.to_raw(max))
+    }
+
+    fn adjust(&self, delta: i32) -> Result<(), Error> {
+        let max = self.max_raw()?;
+        let raw = self.get_raw()?;
+        // Values already below the floor aren't raised by stepping down.
+        let min = floor::min_raw(self.min_level(), max).min(raw);
+        let target = adjusted_raw(raw, max, delta, self.adjust_bounds, self.correction()).max(min);
+        if target == raw {
+            return Ok(());
+        }
+        self.set_raw(target)
+    }
+}
+
+/// Blocking interface to monitor settings exposed as VCP features through DDC/CI.
+///
+/// Only monitors controlled through DDC/CI support these settings; other devices fail with
+/// [`Error::GettingDeviceInfo`]. Monitors commonly implement a subset of the features.
+///
+/// On Linux, monitors handled by the ddcci kernel driver are backlight devices like any other,
+/// and don't support these settings either, as the driver holds their I2C address. Unloading
+/// the driver makes them available over `/dev/i2c-*` instead.
+pub trait MonitorControl {
+    /// Returns the value of a VCP feature.
+    fn get_vcp(&self, code: u8) -> Result<VcpValue, Error>;
+
+    /// Sets the value of a VCP feature.
+    fn set_vcp(&self, code: u8, value: u16) -> Result<(), Error>;
+
+    /// Returns the MCCS capabilities string. Reading it can take seconds.
+    fn capabilities_string(&self) -> Result<String, Error>;
+
+    /// Returns the parsed MCCS capabilities, e.g. to know which VCP features are supported.
+    fn capabilities(&self) -> Result<Capabilities, Error> {
+        self.capabilities_string()?.parse()
+    }
+
+    /// Returns the contrast.
+    fn contrast(&self) -> Result<VcpValue, Error> {
+        self.get_vcp(vcp::CONTRAST)
+    }
+
+    /// Sets the contrast, between 0 and its maximum.
+    fn set_contrast(&self, value: u16) -> Result<(), Error> {
+        self.set_vcp(vcp::CONTRAST, value)
+    }
+
+    /// Returns the audio speaker volume.
+    fn volume(&self) -> Result<VcpValue, Error> {
+        self.get_vcp(vcp::VOLUME)
+    }
+
+    /// Sets the audio speaker volume, between 0 and its maximum.
+    fn set_volume(&self, value: u16) -> Result<(), Error> {
+        self.set_vcp(vcp::VOLUME, value)
+    }
+
+    /// Returns the selected video input.
+    fn input_source(&self) -> Result<InputSource, Error> {
+        Ok(InputSource::from_value(
+            self.get_vcp(vcp::INPUT_SOURCE)?.selected(),
+        ))
+    }
+
+    /// Selects the video input.
+    fn set_input_source(&self, input: InputSource) -> Result<(), Error> {
+        self.set_vcp(vcp::INPUT_SOURCE, input.value().into())
+    }
+
+    /// Returns the power mode.
+    fn power_mode(&self) -> Result<PowerMode, Error> {
+        Ok(PowerMode::from_value(
+            self.get_vcp(vcp::POWER_MODE)?.selected(),
+        ))
+    }
+
+    /// Sets the power mode. Monitors may stop answering until turned back on.
+    fn set_power_mode(&self, mode: PowerMode) -> Result<(), Error> {
+        self.set_vcp(vcp::POWER_MODE, mode.value().into())
+    }
+
+    /// Returns the color preset.
+    fn color_preset(&self) -> Result<ColorPreset, Error> {
+        Ok(ColorPreset::from_value(
+            self.get_vcp(vcp::COLOR_PRESET)?.selected(),
+        ))
+    }
+
+    /// Selects a color preset.
+    fn set_color_preset(&self, preset: ColorPreset) -> Result<(), Error> {
+        self.set_vcp(vcp::COLOR_PRESET, preset.value().into())
+    }
+}
+
+impl MonitorControl for BrightnessDevice {
+    fn get_vcp(&self, code: u8) -> Result<VcpValue, Error> {
+        match &self.inner {
+            DeviceImpl::Platform(d) => Ok(d.get_vcp(code)?),
+            #[cfg(feature = "mock")]
+            DeviceImpl::Mock(d) => Ok(d.get_vcp(code)?),
+        }
+    }
+
+    fn set_vcp(&self, code: u8, value: u16) -> Result<(), Error> {
+        match &self.inner {
+            DeviceImpl::Platform(d) => Ok(d.set_vcp(code, value)?),
+            #[cfg(feature = "mock")]
+            DeviceImpl::Mock(d) => Ok(d.set_vcp(code, value)?),
+        }
+    }
+
+    fn capabilities_string(&self) -> Result<String, Error> {
+        match &self.inner {
+            DeviceImpl::Platform(d) => Ok(d.capabilities_string()?),
+            #[cfg(feature = "mock")]
+            DeviceImpl::Mock(d) => Ok(d.capabilities_string()?),
+        }
     }
 }
 
 /// Blocking function that returns all brightness devices on the running system.
 pub fn brightness_devices() -> impl Iterator<Item = Result<BrightnessDevice, Error>> {
-    platform::brightness_devices().map(|r| r.map(BrightnessDevice).map_err(Into::into))
+    platform::brightness_devices().map(|r| {
+        r.map(|d| BrightnessDevice::new(DeviceImpl::Platform(d)))
+            .map_err(Into::into)
+    })
+}
+
+/// Blocking function that returns the device with the given identifier on the running system, if
+/// it is present.
+pub fn brightness_device_by_id(id: &DeviceId) -> Result<Option<BrightnessDevice>, Error> {
+    find_by_id(brightness_devices(), id)
+}
+
+/// Returns the first of the devices with the given identifier, or the first error.
+pub(crate) fn find_by_id(
+    devices: impl Iterator<Item = Result<BrightnessDevice, Error>>,
+    id: &DeviceId,
+) -> Result<Option<BrightnessDevice>, Error> {
+    for device in devices {
+        let device = device?;
+        if device.id() == *id {
+            return Ok(Some(device));
+        }
+    }
+    Ok(None)
+}
+
+/// Blocking function that sets the brightness of all devices on the running system as a
+/// percentage.
+///
+/// Devices are set concurrently, each in its own thread, so that a slow monitor doesn't delay the
+/// others. Failures are reported per device instead of stopping the operation.
+pub fn set_all(percentage: u32) -> BatchReport<BrightnessDevice, ()> {
+    batch::run(brightness_devices(), |d| d.set(percentage))
+}
+
+/// Blocking function that returns the brightness of all devices on the running system as a
+/// percentage.
+///
+/// Devices are read concurrently like in [`set_all`].
+pub fn get_all() -> BatchReport<BrightnessDevice, u32> {
+    batch::run(brightness_devices(), Brightness::get)
+}
+
+/// Blocking function that returns an iterator over device events on the running system, starting
+/// with an [`Added`](crate::DeviceEvent::Added) event for each existing device.
+///
+/// On Linux, devices are tracked using kernel uevents. Changes that aren't notified and other
+/// platforms are noticed by checking every second.
+pub fn device_events() -> impl Iterator<Item = Result<crate::DeviceEvent<BrightnessDevice>, Error>>
+{
+    cfg_if::cfg_if! {
+        if #[cfg(target_os = "linux")] {
+            platform::Context::new().blocking_device_events()
+        } else {
+            crate::hotplug::blocking_events(crate::hotplug::Source::System)
+        }
+    }
+}
+
+/// Sorts devices by [`DeviceType`] preference and removes raw devices controlling the same panel as
+/// a firmware or platform device, like systemd-backlight does.
+pub fn dedup_backlights(devices: Vec<BrightnessDevice>) -> Vec<BrightnessDevice> {
+    backlight::dedup(devices, |d| (d.device_type(), d.parent()))
+}
+
+/// Blocking function that returns the preferred brightness device on the running system, if any.
+///
+/// See [`dedup_backlights`] for how devices are ranked.
+pub fn primary_backlight() -> Result<Option<BrightnessDevice>, Error> {
+    let devices = brightness_devices().collect::<Result<Vec<_>, _>>()?;
+    Ok(dedup_backlights(devices).into_iter().next())
+}
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use super::*;
+    use crate::mock::MockBackend;
+
+    #[test]
+    fn out_of_range_percentages_are_rejected() {
+        let backend = MockBackend::new().with_device("panel", 100);
+        backend.set_value("panel", 40);
+        let device = backend
+            .blocking_brightness_devices()
+            .next()
+            .unwrap()
+            .unwrap();
+        assert!(matches!(device.set(101), Err(Error::LevelOutOfRange(_))));
+        assert_eq!(backend.value("panel"), Some(40));
+    }
+
+    #[test]
+    fn percentages_and_levels_round_the_same() {
+        let backend = MockBackend::new().with_device("panel", 25);
+        let device = backend
+            .blocking_brightness_devices()
+            .next()
+            .unwrap()
+            .unwrap();
+        for percentage in 0..=100 {
+            device.set(percentage).unwrap();
+            let value = backend.value("panel");
+            device
+                .set_level(Level::from_percentage(percentage).unwrap())
+                .unwrap();
+            assert_eq!(backend.value("panel"), value, "{percentage}%");
+        }
+        device.set(58).unwrap();
+        assert_eq!(backend.value("panel"), Some(15));
+    }
+
+    #[test]
+    fn perception_is_skipped_for_non_linear_scales() {
+        let backend = MockBackend::new()
+            .with_device("firmware", 1000)
+            .with_device("panel", 1000);
+        backend.set_scale("firmware", Scale::NonLinear);
+        for device in backend.blocking_brightness_devices() {
+            let mut device = device.unwrap();
+            device.set_perception(Perception::Cie1931);
+            device.set(50).unwrap();
+            assert_eq!(device.get().unwrap(), 50);
+        }
+        assert_eq!(backend.value("firmware"), Some(500));
+        assert_eq!(backend.value("panel"), Some(184));
+    }
+
+    #[test]
+    fn built_in_backlight_is_ranked_before_external_monitor() {
+        let backend = MockBackend::new()
+            .with_device("ddcci7", 100)
+            .with_device("intel_backlight", 96000);
+        backend.set_device_type("intel_backlight", DeviceType::Raw);
+        let devices = backend
+            .blocking_brightness_devices()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let names = dedup_backlights(devices)
+            .iter()
+            .map(|d| d.device_name().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["intel_backlight", "ddcci7"]);
+    }
 }
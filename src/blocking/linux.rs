@@ -2,144 +2,1795 @@
 
 //! Platform-specific implementation for Linux.
 
-use crate::Error;
+use crate::{
+    DeviceType, Error, Scale, VcpValue,
+    backlight::Parent,
+    blocking::{BrightnessDevice, DeviceImpl},
+    conversion::{percentage_to_raw, raw_to_percentage},
+    ddc::{DDC_ADDRESS, Ddc, DdcError, I2cTransport},
+    vcp,
+    watch::Watcher,
+};
 use itertools::Either;
-use std::{fs, io, iter::once, path::PathBuf};
+use rustix::{
+    event::{PollFd, PollFlags, Timespec, poll},
+    fs::inotify,
+    io::Errno,
+    ioctl::{IntegerSetter, Opcode, ioctl},
+    net::{
+        AddressFamily, SocketFlags, SocketType, bind,
+        netlink::{self, SocketAddrNetlink},
+        socket_with,
+    },
+};
+use std::{
+    error::Error as StdError,
+    fs::{self, File},
+    io::{self, Read, Seek, Write},
+    iter::{once, once_with, successors},
+    mem,
+    os::fd::OwnedFd,
+    path::{Path, PathBuf},
+    sync::{Mutex, MutexGuard, OnceLock, PoisonError},
+    thread,
+    time::{Duration, Instant},
+};
+use zbus::zvariant::{ObjectPath, OwnedObjectPath, OwnedValue};
 
-pub(crate) const BACKLIGHT_DIR: &str = "/sys/class/backlight";
+pub(crate) const SYSFS_ROOT: &str = "/sys";
+pub(crate) const BACKLIGHT_CLASS: &str = "class/backlight";
+pub(crate) const LEDS_CLASS: &str = "class/leds";
+pub(crate) const DRM_CLASS: &str = "class/drm";
 pub(crate) const USER_DBUS_NAME: &str = "org.freedesktop.login1";
 pub(crate) const SESSION_OBJECT_PATH: &str = "/org/freedesktop/login1/session/auto";
 pub(crate) const SESSION_INTERFACE: &str = "org.freedesktop.login1.Session";
 pub(crate) const SET_BRIGHTNESS_METHOD: &str = "SetBrightness";
+pub(crate) const MANAGER_OBJECT_PATH: &str = "/org/freedesktop/login1";
+pub(crate) const MANAGER_INTERFACE: &str = "org.freedesktop.login1.Manager";
+pub(crate) const SEAT_INTERFACE: &str = "org.freedesktop.login1.Seat";
+pub(crate) const PROPERTIES_INTERFACE: &str = "org.freedesktop.DBus.Properties";
+
+/// System bus connection shared by all contexts, established on first use.
+static SYSTEM_BUS: Mutex<Option<zbus::Connection>> = Mutex::new(None);
+
+/// Delay after which devices are enumerated again when a device directory changed.
+const RESCAN_DELAY: Duration = Duration::from_millis(100);
+
+/// Sessions of logind on the system bus found not to provide `SetBrightness`.
+static SYSTEM_LOGIND_UNSUPPORTED: Mutex<Vec<LogindSession>> = Mutex::new(Vec::new());
+
+/// Connector types of built-in panels, whose brightness is controlled by backlight devices.
+const INTERNAL_CONNECTORS: [&str; 3] = ["eDP", "LVDS", "DSI"];
+
+/// ioctl request selecting the address of the I2C device to talk to.
+const I2C_SLAVE: Opcode = 0x0703;
+
+/// Results of probing monitors for DDC/CI support.
+static DDC_PROBES: Mutex<DdcProbes> = Mutex::new(DdcProbes(Vec::new()));
+
+/// Time after which monitors found not to support DDC/CI are probed again, as some monitors only
+/// answer once they are fully powered up.
+const DDC_PROBE_RETRY: Duration = Duration::from_secs(60);
+
+/// `bl_power` value of a powered up backlight (`FB_BLANK_UNBLANK`).
+const BL_POWER_ON: u32 = 0;
+/// `bl_power` value of a powered down backlight (`FB_BLANK_POWERDOWN`).
+const BL_POWER_OFF: u32 = 4;
+
+/// D-Bus errors meaning that logind does not provide `SetBrightness` at all.
+///
+/// `UnknownObject` is not one of them, as it also means that the session doesn't exist, e.g.
+/// because the process is not part of a session or because the session just ended.
+const UNSUPPORTED_ERRORS: [&str; 3] = [
+    "org.freedesktop.DBus.Error.ServiceUnknown",
+    "org.freedesktop.DBus.Error.UnknownInterface",
+    "org.freedesktop.DBus.Error.UnknownMethod",
+];
+
+/// Linux-specific brightness functionality.
+pub trait BrightnessExt {
+    /// Returns how the next brightness change will be written first.
+    ///
+    /// Monitors controlled over I2C always use [`WriteMethod::Ddc`]. For backlight devices, with a
+    /// strategy that falls back, the method that worked after the other one can't be used, e.g.
+    /// because writing sysfs is not permitted or logind lacks `SetBrightness`, is then always
+    /// used for the device. Other failures only fall back for the change at hand. If logind on
+    /// the system bus turns out not to support setting brightness for a [`LogindSession`], it is
+    /// no longer tried for any device using that session unless the strategy is
+    /// [`WriteStrategy::LogindOnly`].
+    fn write_method(&self) -> Result<WriteMethod, Error>;
+
+    /// Returns the order in which write methods are tried.
+    fn write_strategy(&self) -> Result<WriteStrategy, Error>;
+
+    /// Sets the order in which write methods are tried, overriding the strategy of the
+    /// [`Context`] the device was found in. This has no effect on monitors controlled over I2C.
+    fn set_write_strategy(&mut self, strategy: WriteStrategy) -> Result<(), Error>;
+
+    /// Returns whether the backlight is powered, as reported by its `bl_power` file.
+    ///
+    /// Only backlight devices have a power state; LEDs and monitors controlled over I2C fail with
+    /// [`Error::GettingDeviceInfo`].
+    fn power_state(&self) -> Result<PowerState, Error>;
+
+    /// Powers the backlight up or down by writing its `bl_power` file, which requires permission
+    /// as logind can't set it.
+    ///
+    /// Unlike setting brightness to 0, powering down turns the panel off on all hardware. The
+    /// brightness that was set is restored when powering up, as some drivers reset it.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use brightness::blocking::linux::{BrightnessExt, Context, PowerState};
+    /// use std::fs;
+    ///
+    /// let root = std::env::temp_dir().join(format!("brightness-power-{}", std::process::id()));
+    /// let device = root.join("class/backlight/panel");
+    /// fs::create_dir_all(&device).unwrap();
+    /// fs::write(device.join("max_brightness"), "100").unwrap();
+    /// fs::write(device.join("actual_brightness"), "40").unwrap();
+    /// fs::write(device.join("brightness"), "40").unwrap();
+    /// fs::write(device.join("bl_power"), "0").unwrap();
+    ///
+    /// let context = Context::with_sysfs_root(&root);
+    /// let dev = context.blocking_brightness_devices().next().unwrap().unwrap();
+    /// assert_eq!(dev.power_state().unwrap(), PowerState::On);
+    /// dev.set_power(PowerState::Off).unwrap();
+    /// assert_eq!(fs::read_to_string(device.join("bl_power")).unwrap(), "4");
+    /// assert_eq!(dev.power_state().unwrap(), PowerState::Off);
+    ///
+    /// // The driver resets brightness while the panel is off.
+    /// fs::write(device.join("actual_brightness"), "0").unwrap();
+    /// dev.set_power(PowerState::On).unwrap();
+    /// assert_eq!(fs::read_to_string(device.join("bl_power")).unwrap(), "0");
+    /// assert_eq!(fs::read_to_string(device.join("brightness")).unwrap(), "40");
+    /// fs::remove_dir_all(&root).unwrap();
+    /// ```
+    fn set_power(&self, state: PowerState) -> Result<(), Error>;
+}
+
+/// Power state of a backlight.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum PowerState {
+    /// The backlight is on.
+    On,
+    /// The backlight is powered down, blanking the panel.
+    Off,
+}
+
+/// How brightness is written.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum WriteMethod {
+    /// Through the `SetBrightness` method of the logind session.
+    Logind,
+    /// By writing the `brightness` file in sysfs.
+    Sysfs,
+    /// Through DDC/CI, for monitors controlled over I2C.
+    Ddc,
+}
+
+/// Order in which write methods are tried.
+///
+/// Logind is only used for the system sysfs root or with a provided D-Bus connection. Otherwise,
+/// sysfs is always written.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum WriteStrategy {
+    /// Only use logind.
+    LogindOnly,
+    /// Only write sysfs, e.g. when no logind session is available.
+    SysfsOnly,
+    /// Use logind, and write sysfs if it fails.
+    #[default]
+    LogindThenSysfs,
+    /// Write sysfs, and use logind if it fails.
+    SysfsThenLogind,
+}
+
+/// Logind session whose `SetBrightness` method is called.
+///
+/// Sessions other than [`LogindSession::Auto`] are looked up each time brightness is set, so that
+/// e.g. the active session of a seat is followed as it changes.
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
+pub enum LogindSession {
+    /// The session of the calling process.
+    #[default]
+    Auto,
+    /// The session with the given ID.
+    Id(String),
+    /// The active session of the given seat, e.g. `seat0`.
+    ActiveOnSeat(String),
+    /// The session of the process with the given PID.
+    Pid(u32),
+}
+
+/// Class of sysfs devices whose brightness is controlled.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum DeviceClass {
+    /// Display backlights, found in `/sys/class/backlight`, and monitors controlled over I2C.
+    Backlight,
+    /// Keyboard backlights, i.e. LEDs named `*::kbd_backlight` in `/sys/class/leds`.
+    KeyboardLed,
+    /// Other dimmable LEDs in `/sys/class/leds`.
+    Led,
+}
+
+impl DeviceClass {
+    /// Returns the class of an LED given its name.
+    fn of_led(name: &str) -> Self {
+        if name.ends_with("::kbd_backlight") {
+            DeviceClass::KeyboardLed
+        } else {
+            DeviceClass::Led
+        }
+    }
+
+    /// Returns the sysfs subsystem of the class, as expected by logind.
+    pub(crate) fn subsystem(self) -> &'static str {
+        match self {
+            DeviceClass::Backlight => "backlight",
+            DeviceClass::KeyboardLed | DeviceClass::Led => "leds",
+        }
+    }
+
+    fn dir(self) -> &'static str {
+        match self {
+            DeviceClass::Backlight => BACKLIGHT_CLASS,
+            DeviceClass::KeyboardLed | DeviceClass::Led => LEDS_CLASS,
+        }
+    }
+}
+
+/// Brightness device enumeration rooted at a configurable sysfs directory.
+///
+/// The default context looks for devices in `/sys/class/backlight`. Any other directory can be
+/// used as sysfs root as long as it follows the same layout, i.e. `class/backlight/$DEVICE` holding
+/// `actual_brightness`, `max_brightness` and `brightness`. This makes it possible to run against a
+/// fake backlight tree. LEDs in `class/leds`, which have no `actual_brightness`, are also found when
+/// selected with [`set_device_classes`](Context::set_device_classes).
+///
+/// Brightness is only set through logind when using the default sysfs root, as logind always
+/// operates on the real devices, or when a D-Bus connection is provided with
+/// [`set_dbus_connection`](Context::set_dbus_connection). Otherwise, the `brightness` file is
+/// written directly. When logind can be used, [`WriteStrategy`] selects whether it or the
+/// `brightness` file is tried first.
+///
+/// Logind is reached through a system bus connection shared by all contexts. It is established
+/// when first needed, and again if it is lost.
+///
+/// # Example
+///
+/// ```rust
+/// use brightness::blocking::{Brightness, linux::Context};
+/// use std::fs;
+///
+/// let root = std::env::temp_dir().join(format!("brightness-doctest-{}", std::process::id()));
+/// let device = root.join("class/backlight/panel");
+/// fs::create_dir_all(&device).unwrap();
+/// fs::write(device.join("max_brightness"), "200").unwrap();
+/// fs::write(device.join("actual_brightness"), "50").unwrap();
+///
+/// let context = Context::with_sysfs_root(&root);
+/// for dev in context.blocking_brightness_devices() {
+///     let dev = dev.unwrap();
+///     assert_eq!(dev.device_name().unwrap(), "panel");
+///     assert_eq!(dev.get().unwrap(), 25);
+///     dev.set(100).unwrap();
+///     assert_eq!(fs::read_to_string(device.join("brightness")).unwrap(), "200");
+/// }
+/// fs::remove_dir_all(&root).unwrap();
+/// ```
+#[derive(Clone, Debug)]
+pub struct Context {
+    sysfs_root: PathBuf,
+    dbus_connection: Option<zbus::Connection>,
+    write_strategy: WriteStrategy,
+    logind_session: LogindSession,
+    ddc_enabled: bool,
+    /// Address used instead of the default one when connecting to the system bus.
+    system_bus_address: Option<String>,
+    device_classes: Vec<DeviceClass>,
+}
+
+impl Context {
+    /// Returns a context using the system sysfs root (`/sys`).
+    pub fn new() -> Self {
+        Self::with_sysfs_root(SYSFS_ROOT)
+    }
+
+    /// Returns a context using the given directory as sysfs root.
+    pub fn with_sysfs_root<P: Into<PathBuf>>(root: P) -> Self {
+        Self {
+            sysfs_root: root.into(),
+            dbus_connection: None,
+            write_strategy: WriteStrategy::default(),
+            logind_session: LogindSession::default(),
+            ddc_enabled: true,
+            system_bus_address: None,
+            device_classes: vec![DeviceClass::Backlight],
+        }
+    }
+
+    /// Returns the sysfs root directory.
+    pub fn sysfs_root(&self) -> &Path {
+        &self.sysfs_root
+    }
+
+    /// Returns the D-Bus connection provided to reach logind, if any.
+    pub fn dbus_connection(&self) -> Option<&zbus::Connection> {
+        self.dbus_connection.as_ref()
+    }
+
+    /// Sets the D-Bus connection used to reach logind instead of the shared system bus connection.
+    ///
+    /// Logind is then used whatever the sysfs root, e.g. to run against a private bus. The
+    /// connection is not replaced if it is lost.
+    pub fn set_dbus_connection(&mut self, connection: zbus::Connection) {
+        self.dbus_connection = Some(connection);
+    }
+
+    /// Returns the logind session used to set brightness.
+    pub fn logind_session(&self) -> &LogindSession {
+        &self.logind_session
+    }
+
+    /// Sets the logind session used to set brightness. The default is [`LogindSession::Auto`],
+    /// which requires the calling process to be part of a session.
+    pub fn set_logind_session(&mut self, session: LogindSession) {
+        self.logind_session = session;
+    }
+
+    /// Returns the order in which write methods are tried for devices found in this context.
+    pub fn write_strategy(&self) -> WriteStrategy {
+        self.write_strategy
+    }
+
+    /// Sets the order in which write methods are tried for devices found in this context. The
+    /// default is [`WriteStrategy::LogindThenSysfs`].
+    pub fn set_write_strategy(&mut self, strategy: WriteStrategy) {
+        self.write_strategy = strategy;
+    }
+
+    /// Returns whether monitors are controlled directly over I2C.
+    pub fn ddc_enabled(&self) -> bool {
+        self.ddc_enabled
+    }
+
+    /// Sets whether monitors are controlled directly over I2C, which is enabled by default.
+    ///
+    /// Monitors connected to the system are then found through `/dev/i2c-*` without requiring the
+    /// ddcci kernel driver, provided the process can access these devices (e.g. by being in the
+    /// `i2c` group). Finding them for the first time takes a noticeable amount of time, as each
+    /// one is probed. Monitors are never looked for with a sysfs root other than `/sys`.
+    pub fn set_ddc_enabled(&mut self, enabled: bool) {
+        self.ddc_enabled = enabled;
+    }
+
+    /// Returns the classes of devices found in this context.
+    pub fn device_classes(&self) -> &[DeviceClass] {
+        &self.device_classes
+    }
+
+    /// Sets the classes of devices found in this context. The default is
+    /// [`DeviceClass::Backlight`] only.
+    ///
+    /// Backlights and monitors are listed first, then keyboard backlights, then other LEDs.
+    pub fn set_device_classes<I: IntoIterator<Item = DeviceClass>>(&mut self, classes: I) {
+        self.device_classes = classes.into_iter().collect();
+    }
+
+    /// Blocking function that returns an iterator over device events in this context, starting
+    /// with an [`Added`](crate::DeviceEvent::Added) event for each existing device.
+    ///
+    /// Devices of the real sysfs are tracked using kernel uevents, and devices of other sysfs
+    /// roots using inotify. Changes that aren't notified are noticed by checking every second.
+    pub fn blocking_device_events(
+        &self,
+    ) -> impl Iterator<Item = Result<crate::DeviceEvent<BrightnessDevice>, Error>> + use<> {
+        crate::hotplug::blocking_events(crate::hotplug::Source::Sysfs(self.clone()))
+    }
+
+    /// Blocking function that returns all brightness devices found in this context.
+    pub fn blocking_brightness_devices(
+        &self,
+    ) -> impl Iterator<Item = Result<BrightnessDevice, Error>> + use<> {
+        self.devices().map(|r| {
+            r.map(|d| BrightnessDevice::new(DeviceImpl::Platform(d)))
+                .map_err(Into::into)
+        })
+    }
+
+    /// Returns the names of the backlight devices found in this context.
+    fn backlight_names(&self) -> impl Iterator<Item = Result<String, SysError>> + use<> {
+        self.device_classes
+            .contains(&DeviceClass::Backlight)
+            .then(|| self.class_device_names(DeviceClass::Backlight))
+            .into_iter()
+            .flatten()
+    }
+
+    /// Returns the classes and names of the LEDs found in this context, keyboard backlights first.
+    /// LEDs that can only be switched on and off, i.e. whose maximum brightness is 1, are skipped.
+    fn led_names(&self) -> Vec<Result<(DeviceClass, String), SysError>> {
+        if !self.device_classes.iter().any(|c| c.dir() == LEDS_CLASS) {
+            return Vec::new();
+        }
+        let mut leds = self
+            .class_device_names(DeviceClass::Led)
+            .filter_map(|name| match name {
+                Ok(name) => {
+                    let class = DeviceClass::of_led(&name);
+                    let dimmable =
+                        || read_value(self, class, &name, Value::Max).is_ok_and(|max| max > 1);
+                    (self.device_classes.contains(&class) && dimmable())
+                        .then_some(Ok((class, name)))
+                }
+                Err(e) => Some(Err(e)),
+            })
+            .collect::<Vec<_>>();
+        leds.sort_by_key(|led| !matches!(led, Ok((DeviceClass::KeyboardLed, _))));
+        leds
+    }
+
+    /// Returns the names of the devices in the sysfs directory of a class that report their
+    /// brightness.
+    fn class_device_names(
+        &self,
+        class: DeviceClass,
+    ) -> impl Iterator<Item = Result<String, SysError>> + use<> {
+        let dir = self.class_dir(class);
+        match fs::read_dir(&dir) {
+            Ok(devices) => Either::Left(
+                devices
+                    .map(move |device| {
+                        let device = device.map_err(|source| SysError::ReadingBacklightDir {
+                            path: dir.clone(),
+                            source,
+                        })?;
+                        let path = device.path();
+                        let keep = path.join(Value::Actual.file_name(class)).exists()
+                            && path.join(Value::Max.file_name(class)).exists();
+                        Ok(device.file_name().into_string().ok().filter(|_| keep))
+                    })
+                    .filter_map(Result::transpose),
+            ),
+            Err(source) => Either::Right(once(Err(SysError::ReadingBacklightDir {
+                path: dir,
+                source,
+            }))),
+        }
+    }
+
+    pub(crate) fn devices(
+        &self,
+    ) -> impl Iterator<Item = Result<BlockingDeviceImpl, SysError>> + use<> {
+        let sysfs = {
+            let context = self.clone();
+            move |name: Result<(DeviceClass, String), SysError>| {
+                let (class, device) = name?;
+                Ok(BlockingDeviceImpl::Sysfs(SysfsDevice {
+                    context: context.clone(),
+                    class,
+                    device,
+                    write_method: OnceLock::new(),
+                }))
+            }
+        };
+        let monitors = once_with({
+            let context = self.clone();
+            move || context.ddc_devices()
+        });
+        let leds = once_with({
+            let context = self.clone();
+            move || context.led_names()
+        });
+        self.backlight_names()
+            .map(|name| Ok((DeviceClass::Backlight, name?)))
+            .map(sysfs.clone())
+            .chain(
+                monitors
+                    .flatten()
+                    .map(|d| Ok(BlockingDeviceImpl::Ddc(Box::new(d)))),
+            )
+            .chain(leds.flatten().map(sysfs))
+    }
+
+    /// Returns the monitors that can be controlled over I2C.
+    ///
+    /// Connected external connectors are probed for DDC/CI support, unless the ddcci kernel driver
+    /// already provides a backlight device for them. Monitors that can't be opened or don't report
+    /// their luminance are skipped. Probe results are remembered per bus and monitor.
+    fn ddc_devices(&self) -> Vec<DdcDevice> {
+        if !self.ddc_enabled
+            || !self.is_system()
+            || !self.device_classes.contains(&DeviceClass::Backlight)
+        {
+            return Vec::new();
+        }
+        let mut devices = Connector::all(self)
+            .into_iter()
+            .filter_map(|connector| {
+                if connector.is_internal() || !connector.is_connected() {
+                    return None;
+                }
+                let bus = connector.bus()?;
+                if self
+                    .device_dir(DeviceClass::Backlight, &format!("ddcci{bus}"))
+                    .exists()
+                {
+                    return None;
+                }
+                DdcDevice::probe(self, bus, connector)
+            })
+            .collect::<Vec<_>>();
+        devices.sort_by_key(|d| d.bus);
+        devices
+    }
+
+    pub(crate) fn class_dir(&self, class: DeviceClass) -> PathBuf {
+        self.sysfs_root.join(class.dir())
+    }
+
+    pub(crate) fn device_dir(&self, class: DeviceClass, device: &str) -> PathBuf {
+        self.class_dir(class).join(device)
+    }
+
+    /// Returns whether this context operates on the real devices, in which case logind and kernel
+    /// uevents can be used.
+    pub(crate) fn is_system(&self) -> bool {
+        self.sysfs_root == Path::new(SYSFS_ROOT)
+    }
+
+    /// Returns the address to connect to the system bus at, if not the default one.
+    pub(crate) fn system_bus_address(&self) -> Option<&str> {
+        self.system_bus_address.as_deref()
+    }
+
+    /// Returns whether brightness is set through logind.
+    pub(crate) fn uses_logind(&self) -> bool {
+        self.is_system() || self.dbus_connection.is_some()
+    }
+
+    /// Returns the method to write brightness with and the method to fall back to, given the
+    /// method remembered for the device.
+    pub(crate) fn write_plan(
+        &self,
+        remembered: &OnceLock<WriteMethod>,
+    ) -> (WriteMethod, Option<WriteMethod>) {
+        let logind_unsupported = self.dbus_connection.is_none()
+            && lock_logind_unsupported().contains(&self.logind_session);
+        match (self.write_strategy, remembered.get()) {
+            _ if !self.uses_logind() => (WriteMethod::Sysfs, None),
+            (WriteStrategy::LogindOnly, _) => (WriteMethod::Logind, None),
+            (WriteStrategy::SysfsOnly, _) => (WriteMethod::Sysfs, None),
+            _ if logind_unsupported => (WriteMethod::Sysfs, None),
+            (_, Some(&method)) => (method, None),
+            (WriteStrategy::LogindThenSysfs, None) => {
+                (WriteMethod::Logind, Some(WriteMethod::Sysfs))
+            }
+            (WriteStrategy::SysfsThenLogind, None) => {
+                (WriteMethod::Sysfs, Some(WriteMethod::Logind))
+            }
+        }
+    }
+
+    /// Remembers whether logind on the system bus supports setting brightness for the session of
+    /// this context.
+    pub(crate) fn check_logind(
+        &self,
+        device: &str,
+        result: zbus::Result<()>,
+    ) -> Result<(), SysError> {
+        let error = match result {
+            Ok(()) => return Ok(()),
+            Err(source) => SysError::SettingBrightnessThroughLogind {
+                device: device.into(),
+                source: source.into(),
+            },
+        };
+        if self.dbus_connection.is_none() && error.is_unsupported_write() {
+            // Setting brightness through dbus does not work on older systems that don't have the
+            // `SetBrightness` method.
+            let mut unsupported = lock_logind_unsupported();
+            if !unsupported.contains(&self.logind_session) {
+                unsupported.push(self.logind_session.clone());
+            }
+        }
+        Err(error)
+    }
+
+    /// Asks logind to set the brightness of a device, reconnecting to the system bus once if the
+    /// shared connection was lost.
+    fn set_brightness(&self, class: DeviceClass, device: &str, value: u32) -> zbus::Result<()> {
+        let connection = match &self.dbus_connection {
+            Some(connection) => {
+                return self.call_set_brightness(&connection.clone().into(), class, device, value);
+            }
+            None => system_bus(self)?,
+        };
+        match self.call_set_brightness(&connection, class, device, value) {
+            Err(zbus::Error::InputOutput(_)) => {
+                forget_system_bus(connection.inner());
+                self.call_set_brightness(&system_bus(self)?, class, device, value)
+            }
+            result => result,
+        }
+    }
+
+    fn call_set_brightness(
+        &self,
+        connection: &zbus::blocking::Connection,
+        class: DeviceClass,
+        device: &str,
+        value: u32,
+    ) -> zbus::Result<()> {
+        connection.call_method(
+            Some(USER_DBUS_NAME),
+            &self.session_path(connection)?,
+            Some(SESSION_INTERFACE),
+            SET_BRIGHTNESS_METHOD,
+            &(class.subsystem(), device, value),
+        )?;
+        Ok(())
+    }
+
+    fn session_path(
+        &self,
+        connection: &zbus::blocking::Connection,
+    ) -> zbus::Result<OwnedObjectPath> {
+        let manager = |method, arg: &str| {
+            connection.call_method(
+                Some(USER_DBUS_NAME),
+                MANAGER_OBJECT_PATH,
+                Some(MANAGER_INTERFACE),
+                method,
+                &(arg,),
+            )
+        };
+        match &self.logind_session {
+            LogindSession::Auto => Ok(auto_session_path()),
+            LogindSession::Id(id) => manager("GetSession", id)?.body().deserialize(),
+            LogindSession::ActiveOnSeat(seat) => {
+                let seat: OwnedObjectPath = manager("GetSeat", seat)?.body().deserialize()?;
+                let reply = connection.call_method(
+                    Some(USER_DBUS_NAME),
+                    &seat,
+                    Some(PROPERTIES_INTERFACE),
+                    "Get",
+                    &(SEAT_INTERFACE, "ActiveSession"),
+                )?;
+                active_session_path(reply.body().deserialize()?)
+            }
+            LogindSession::Pid(pid) => connection
+                .call_method(
+                    Some(USER_DBUS_NAME),
+                    MANAGER_OBJECT_PATH,
+                    Some(MANAGER_INTERFACE),
+                    "GetSessionByPID",
+                    &(pid,),
+                )?
+                .body()
+                .deserialize(),
+        }
+    }
+}
+
+impl Default for Context {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 #[derive(Debug)]
-pub(crate) struct BlockingDeviceImpl {
-    device: String,
+pub(crate) enum BlockingDeviceImpl {
+    Sysfs(SysfsDevice),
+    Ddc(Box<DdcDevice>),
 }
 
 impl crate::blocking::Brightness for BlockingDeviceImpl {
     fn device_name(&self) -> Result<String, Error> {
-        Ok(self.device.clone())
+        match self {
+            BlockingDeviceImpl::Sysfs(d) => d.device_name(),
+            BlockingDeviceImpl::Ddc(d) => d.device_name(),
+        }
     }
 
     fn get(&self) -> Result<u32, Error> {
-        let max = read_value(&self.device, Value::Max)?;
-        let actual = read_value(&self.device, Value::Actual)?;
-        let percentage = if max == 0 {
-            0
-        } else {
-            (actual * 100 / max) as u32
-        };
-        Ok(percentage)
+        match self {
+            BlockingDeviceImpl::Sysfs(d) => d.get(),
+            BlockingDeviceImpl::Ddc(d) => d.get(),
+        }
     }
 
     fn set(&self, percentage: u32) -> Result<(), Error> {
-        let percentage = percentage.min(100);
-        let max = read_value(&self.device, Value::Max)?;
-        let desired_value = (u64::from(percentage) * u64::from(max) / 100) as u32;
-        let desired = ("backlight", &self.device, desired_value);
-        let bus = zbus::blocking::Connection::system().map_err(|e| Error::SettingBrightness {
-            device: self.device.clone(),
-            source: e.into(),
-        })?;
-        let response = bus.call_method(
-            Some(USER_DBUS_NAME),
-            SESSION_OBJECT_PATH,
-            Some(SESSION_INTERFACE),
-            SET_BRIGHTNESS_METHOD,
-            &desired,
-        );
-        match response {
-            Ok(_) => Ok(()),
-            Err(zbus::Error::MethodError(..)) => {
-                // Setting brightness through dbus may not work on older systems that don't have
-                // the `SetBrightness` method. Fall back to writing to the brightness file (which
-                // requires permission).
-                set_value(&self.device, desired_value)?;
-                Ok(())
+        match self {
+            BlockingDeviceImpl::Sysfs(d) => d.set(percentage),
+            BlockingDeviceImpl::Ddc(d) => d.set(percentage),
+        }
+    }
+
+    fn get_raw(&self) -> Result<u32, Error> {
+        match self {
+            BlockingDeviceImpl::Sysfs(d) => d.get_raw(),
+            BlockingDeviceImpl::Ddc(d) => d.get_raw(),
+        }
+    }
+
+    fn max_raw(&self) -> Result<u32, Error> {
+        match self {
+            BlockingDeviceImpl::Sysfs(d) => d.max_raw(),
+            BlockingDeviceImpl::Ddc(d) => d.max_raw(),
+        }
+    }
+
+    fn set_raw(&self, value: u32) -> Result<(), Error> {
+        match self {
+            BlockingDeviceImpl::Sysfs(d) => d.set_raw(value),
+            BlockingDeviceImpl::Ddc(d) => d.set_raw(value),
+        }
+    }
+}
+
+impl BlockingDeviceImpl {
+    pub(crate) fn scale(&self) -> Scale {
+        match self {
+            BlockingDeviceImpl::Sysfs(d) => d.scale(),
+            BlockingDeviceImpl::Ddc(_) => Scale::Unknown,
+        }
+    }
+
+    pub(crate) fn device_type(&self) -> DeviceType {
+        match self {
+            BlockingDeviceImpl::Sysfs(d) => d.device_type(),
+            BlockingDeviceImpl::Ddc(_) => DeviceType::Unknown,
+        }
+    }
+
+    pub(crate) fn parent(&self) -> Option<Parent> {
+        match self {
+            BlockingDeviceImpl::Sysfs(d) => d.parent(),
+            BlockingDeviceImpl::Ddc(_) => None,
+        }
+    }
+
+    pub(crate) fn watcher(&self) -> Result<Watcher, SysError> {
+        match self {
+            BlockingDeviceImpl::Sysfs(d) => d.watcher(),
+            BlockingDeviceImpl::Ddc(d) => Err(d.watching_unsupported()),
+        }
+    }
+
+    pub(crate) fn write_method(&self) -> WriteMethod {
+        match self {
+            BlockingDeviceImpl::Sysfs(d) => d.context.write_plan(&d.write_method).0,
+            BlockingDeviceImpl::Ddc(_) => WriteMethod::Ddc,
+        }
+    }
+
+    pub(crate) fn write_strategy(&self) -> WriteStrategy {
+        match self {
+            BlockingDeviceImpl::Sysfs(d) => d.context.write_strategy(),
+            BlockingDeviceImpl::Ddc(d) => d.context.write_strategy(),
+        }
+    }
+
+    pub(crate) fn set_write_strategy(&mut self, strategy: WriteStrategy) {
+        match self {
+            BlockingDeviceImpl::Sysfs(d) => d.set_write_strategy(strategy),
+            BlockingDeviceImpl::Ddc(_) => {}
+        }
+    }
+
+    pub(crate) fn get_vcp(&self, code: u8) -> Result<VcpValue, SysError> {
+        match self {
+            BlockingDeviceImpl::Sysfs(d) => Err(d.ddc_unsupported()),
+            BlockingDeviceImpl::Ddc(d) => d.get_vcp(code),
+        }
+    }
+
+    pub(crate) fn set_vcp(&self, code: u8, value: u16) -> Result<(), SysError> {
+        match self {
+            BlockingDeviceImpl::Sysfs(d) => Err(d.ddc_unsupported()),
+            BlockingDeviceImpl::Ddc(d) => d.set_vcp(code, value),
+        }
+    }
+
+    pub(crate) fn capabilities_string(&self) -> Result<String, SysError> {
+        match self {
+            BlockingDeviceImpl::Sysfs(d) => Err(d.ddc_unsupported()),
+            BlockingDeviceImpl::Ddc(d) => d.capabilities_string(),
+        }
+    }
+
+    pub(crate) fn connector_name(&self) -> Option<String> {
+        match self {
+            BlockingDeviceImpl::Sysfs(d) => d.connector().map(|c| c.kind()),
+            BlockingDeviceImpl::Ddc(d) => Some(d.connector.kind()),
+        }
+    }
+
+    pub(crate) fn is_internal(&self) -> bool {
+        match self {
+            BlockingDeviceImpl::Sysfs(d) => d.is_internal(),
+            BlockingDeviceImpl::Ddc(_) => false,
+        }
+    }
+
+    /// Returns the EDID of the display. Displays that can't be identified have none.
+    pub(crate) fn edid(&self) -> Result<Option<Vec<u8>>, SysError> {
+        Ok(match self {
+            BlockingDeviceImpl::Sysfs(d) => d.edid(),
+            BlockingDeviceImpl::Ddc(d) => d.edid(),
+        })
+    }
+
+    pub(crate) fn power_state(&self) -> Result<PowerState, SysError> {
+        match self {
+            BlockingDeviceImpl::Sysfs(d) => d.power_state(),
+            BlockingDeviceImpl::Ddc(d) => Err(SysError::PowerUnsupported { device: d.name() }),
+        }
+    }
+
+    pub(crate) fn set_power(&self, state: PowerState) -> Result<(), Error> {
+        match self {
+            BlockingDeviceImpl::Sysfs(d) => d.set_power(state),
+            BlockingDeviceImpl::Ddc(d) => {
+                Err(SysError::PowerUnsupported { device: d.name() }.into())
             }
-            Err(e) => Err(Error::SettingBrightness {
-                device: self.device.clone(),
-                source: e.into(),
-            }),
+        }
+    }
+
+    /// Returns the driver part of the device identifier.
+    pub(crate) fn driver(&self) -> String {
+        match self {
+            BlockingDeviceImpl::Sysfs(d) => driver(&d.device),
+            BlockingDeviceImpl::Ddc(d) => driver(&d.name()),
+        }
+    }
+
+    /// Returns a path identifying the device across reboots.
+    pub(crate) fn stable_path(&self) -> String {
+        match self {
+            BlockingDeviceImpl::Sysfs(d) => d.stable_path(),
+            BlockingDeviceImpl::Ddc(d) => d.connector.stable_path(),
         }
     }
 }
 
-pub(crate) fn brightness_devices() -> impl Iterator<Item = Result<BlockingDeviceImpl, SysError>> {
-    match fs::read_dir(BACKLIGHT_DIR) {
-        Ok(devices) => Either::Left(
-            devices
-                .map(|device| {
-                    let device = device.map_err(SysError::ReadingBacklightDir)?;
-                    let path = device.path();
-                    let keep = path.join(Value::Actual.as_str()).exists()
-                        && path.join(Value::Max.as_str()).exists();
-                    Ok(device
-                        .file_name()
-                        .into_string()
-                        .ok()
-                        .map(|device| BlockingDeviceImpl { device })
-                        .filter(|_| keep))
-                })
-                .filter_map(Result::transpose),
-        ),
-        Err(e) => Either::Right(once(Err(SysError::ReadingBacklightDir(e)))),
+/// Device of the `backlight` or `leds` sysfs class.
+#[derive(Debug)]
+pub(crate) struct SysfsDevice {
+    pub(crate) context: Context,
+    pub(crate) class: DeviceClass,
+    pub(crate) device: String,
+    pub(crate) write_method: OnceLock<WriteMethod>,
+}
+
+impl crate::blocking::Brightness for SysfsDevice {
+    fn device_name(&self) -> Result<String, Error> {
+        Ok(self.device.clone())
+    }
+
+    fn get(&self) -> Result<u32, Error> {
+        let max = read_value(&self.context, self.class, &self.device, Value::Max)?;
+        let actual = read_value(&self.context, self.class, &self.device, Value::Actual)?;
+        Ok(raw_to_percentage(actual, max))
+    }
+
+    fn set(&self, percentage: u32) -> Result<(), Error> {
+        let max = read_value(&self.context, self.class, &self.device, Value::Max)?;
+        let desired_value = percentage_to_raw(percentage, max);
+        self.write_raw(desired_value)
+    }
+
+    fn get_raw(&self) -> Result<u32, Error> {
+        Ok(read_value(
+            &self.context,
+            self.class,
+            &self.device,
+            Value::Actual,
+        )?)
+    }
+
+    fn max_raw(&self) -> Result<u32, Error> {
+        Ok(read_value(
+            &self.context,
+            self.class,
+            &self.device,
+            Value::Max,
+        )?)
+    }
+
+    fn set_raw(&self, value: u32) -> Result<(), Error> {
+        let max = read_value(&self.context, self.class, &self.device, Value::Max)?;
+        self.write_raw(value.min(max))
+    }
+}
+
+impl SysfsDevice {
+    pub(crate) fn scale(&self) -> Scale {
+        read_scale(&self.context, self.class, &self.device)
+    }
+
+    pub(crate) fn device_type(&self) -> DeviceType {
+        read_device_type(&self.context, self.class, &self.device)
+    }
+
+    /// Returns the device the backlight belongs to. LEDs don't control displays, so they have
+    /// none.
+    pub(crate) fn parent(&self) -> Option<Parent> {
+        self.is_backlight()
+            .then(|| read_parent(&self.context, &self.device))
+            .flatten()
+    }
+
+    pub(crate) fn connector(&self) -> Option<Connector> {
+        self.is_backlight()
+            .then(|| Connector::of_backlight(&self.context, &self.device))
+            .flatten()
+    }
+
+    /// Returns whether the device controls a built-in panel. Backlight devices whose connector is
+    /// unknown are assumed to, except for monitors controlled by the ddcci driver.
+    pub(crate) fn is_internal(&self) -> bool {
+        match self.connector() {
+            Some(connector) => connector.is_internal(),
+            None => self.is_backlight() && !self.device.starts_with("ddcci"),
+        }
+    }
+
+    pub(crate) fn edid(&self) -> Option<Vec<u8>> {
+        self.connector()?.edid()
+    }
+
+    /// Returns the sysfs path of the device under its parent device, which identifies sibling
+    /// devices of the same driver, unlike the path of the parent. I2C bus numbers depend on the
+    /// enumeration order, so ddcci devices use the path of their connector instead. Graphics card
+    /// numbers are left out, see [`without_card_numbers`].
+    pub(crate) fn stable_path(&self) -> String {
+        let dir = self.context.device_dir(self.class, &self.device);
+        let connector = self
+            .device
+            .starts_with("ddcci")
+            .then(|| self.connector())
+            .flatten();
+        match connector {
+            Some(connector) => connector.stable_path(),
+            None => without_card_numbers(&fs::canonicalize(&dir).unwrap_or(dir)),
+        }
+    }
+
+    fn is_backlight(&self) -> bool {
+        self.class == DeviceClass::Backlight
+    }
+
+    pub(crate) fn power_state(&self) -> Result<PowerState, SysError> {
+        if !self.is_backlight() {
+            return Err(self.power_unsupported());
+        }
+        Ok(
+            match read_value(&self.context, self.class, &self.device, Value::Power)? {
+                BL_POWER_ON => PowerState::On,
+                _ => PowerState::Off,
+            },
+        )
+    }
+
+    /// Powers the backlight up or down. The kernel keeps the brightness that was set while the
+    /// backlight is off, so it is written again on power-up if the driver reset it.
+    pub(crate) fn set_power(&self, state: PowerState) -> Result<(), Error> {
+        self.power_state()?;
+        match state {
+            PowerState::Off => Ok(write_power(&self.context, &self.device, BL_POWER_OFF)?),
+            PowerState::On => {
+                write_power(&self.context, &self.device, BL_POWER_ON)?;
+                let set = read_value(&self.context, self.class, &self.device, Value::Set)?;
+                let actual = read_value(&self.context, self.class, &self.device, Value::Actual)?;
+                if actual != set {
+                    self.write_raw(set)?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn power_unsupported(&self) -> SysError {
+        SysError::PowerUnsupported {
+            device: self.device.clone(),
+        }
+    }
+
+    pub(crate) fn ddc_unsupported(&self) -> SysError {
+        SysError::DdcUnsupported {
+            device: self.device.clone(),
+        }
+    }
+
+    pub(crate) fn watcher(&self) -> Result<Watcher, SysError> {
+        AttributeWatcher::new(&self.context, self.class, &self.device, Value::Actual)
+            .map(Watcher::Attribute)
+    }
+
+    fn write_raw(&self, value: u32) -> Result<(), Error> {
+        let (method, fallback) = self.context.write_plan(&self.write_method);
+        match (self.write_with(method, value), fallback) {
+            (Err(e), Some(fallback)) => {
+                // Only stop trying a method that can't work, not one that failed once.
+                let unsupported = e.is_unsupported_write();
+                self.write_with(fallback, value)
+                    .map_err(|source| e.falling_back(&self.device, source))?;
+                if unsupported {
+                    let _ = self.write_method.set(fallback);
+                }
+                Ok(())
+            }
+            (result, _) => Ok(result?),
+        }
+    }
+
+    fn write_with(&self, method: WriteMethod, value: u32) -> Result<(), SysError> {
+        match method {
+            WriteMethod::Logind => {
+                let result = self.context.set_brightness(self.class, &self.device, value);
+                self.context.check_logind(&self.device, result)
+            }
+            // Writing to the brightness file requires permission.
+            WriteMethod::Sysfs => set_value(&self.context, self.class, &self.device, value),
+            WriteMethod::Ddc => unreachable!("backlight devices are not written through DDC/CI"),
+        }
+    }
+
+    pub(crate) fn set_write_strategy(&mut self, strategy: WriteStrategy) {
+        self.context.set_write_strategy(strategy);
+        self.write_method = OnceLock::new();
+    }
+}
+
+impl BrightnessExt for BrightnessDevice {
+    fn write_method(&self) -> Result<WriteMethod, Error> {
+        match &self.inner {
+            DeviceImpl::Platform(d) => Ok(d.write_method()),
+            #[cfg(feature = "mock")]
+            DeviceImpl::Mock(d) => Err(d.unsupported()),
+        }
+    }
+
+    fn write_strategy(&self) -> Result<WriteStrategy, Error> {
+        match &self.inner {
+            DeviceImpl::Platform(d) => Ok(d.write_strategy()),
+            #[cfg(feature = "mock")]
+            DeviceImpl::Mock(d) => Err(d.unsupported()),
+        }
+    }
+
+    fn set_write_strategy(&mut self, strategy: WriteStrategy) -> Result<(), Error> {
+        match &mut self.inner {
+            DeviceImpl::Platform(d) => {
+                d.set_write_strategy(strategy);
+                Ok(())
+            }
+            #[cfg(feature = "mock")]
+            DeviceImpl::Mock(d) => Err(d.unsupported()),
+        }
+    }
+
+    fn power_state(&self) -> Result<PowerState, Error> {
+        match &self.inner {
+            DeviceImpl::Platform(d) => Ok(d.power_state()?),
+            #[cfg(feature = "mock")]
+            DeviceImpl::Mock(d) => Err(d.unsupported()),
+        }
+    }
+
+    fn set_power(&self, state: PowerState) -> Result<(), Error> {
+        match &self.inner {
+            DeviceImpl::Platform(d) => d.set_power(state),
+            #[cfg(feature = "mock")]
+            DeviceImpl::Mock(d) => Err(d.unsupported()),
+        }
+    }
+}
+
+pub(crate) fn auto_session_path() -> OwnedObjectPath {
+    ObjectPath::from_static_str_unchecked(SESSION_OBJECT_PATH).into()
+}
+
+/// Returns the object path of the active session of a seat, from its `ActiveSession` property.
+pub(crate) fn active_session_path(property: OwnedValue) -> zbus::Result<OwnedObjectPath> {
+    let (id, path) = <(String, OwnedObjectPath)>::try_from(property)?;
+    if id.is_empty() {
+        return Err(zbus::Error::Failure(
+            "The seat has no active session".into(),
+        ));
+    }
+    Ok(path)
+}
+
+/// Returns the shared system bus connection, connecting if needed.
+fn system_bus(context: &Context) -> zbus::Result<zbus::blocking::Connection> {
+    if let Some(connection) = cached_system_bus() {
+        return Ok(connection.into());
+    }
+    let connection = match context.system_bus_address() {
+        Some(address) => zbus::blocking::connection::Builder::address(address)?.build()?,
+        None => zbus::blocking::Connection::system()?,
+    };
+    Ok(share_system_bus(connection.into_inner()).into())
+}
+
+/// Returns a sysfs path without the `cardN` directories and connector name prefixes of graphics
+/// cards, e.g. `/sys/devices/pci0000:00/0000:00:02.0/drm/DP-1` for
+/// `/sys/devices/pci0000:00/0000:00:02.0/drm/card1/card1-DP-1`. Card numbers depend on the order
+/// in which the cards were probed, while the PCI path before them identifies the card.
+fn without_card_numbers(path: &Path) -> String {
+    path.iter()
+        .map(|component| component.to_string_lossy())
+        .filter_map(|component| match after_card_number(&component) {
+            Some("") => None,
+            Some(rest) => Some(rest.strip_prefix('-').unwrap_or(rest).to_owned()),
+            None => Some(component.into_owned()),
+        })
+        .collect::<PathBuf>()
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Returns what follows `cardN` at the start of `s`, if it starts with a card name.
+fn after_card_number(s: &str) -> Option<&str> {
+    let digits = s.strip_prefix("card")?;
+    let end = digits
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(digits.len());
+    (end > 0).then(|| &digits[end..])
+}
+
+fn lock_logind_unsupported() -> MutexGuard<'static, Vec<LogindSession>> {
+    SYSTEM_LOGIND_UNSUPPORTED
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+}
+
+fn lock_system_bus() -> MutexGuard<'static, Option<zbus::Connection>> {
+    SYSTEM_BUS.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+pub(crate) fn cached_system_bus() -> Option<zbus::Connection> {
+    lock_system_bus().clone()
+}
+
+/// Shares a new system bus connection, unless another one was shared meanwhile. Returns the
+/// shared connection.
+pub(crate) fn share_system_bus(connection: zbus::Connection) -> zbus::Connection {
+    lock_system_bus().get_or_insert(connection).clone()
+}
+
+/// Stops sharing a lost system bus connection, unless it was already replaced.
+pub(crate) fn forget_system_bus(connection: &zbus::Connection) {
+    let mut shared = lock_system_bus();
+    if shared.as_ref().map(|c| c.unique_name()) == Some(connection.unique_name()) {
+        *shared = None;
+    }
+}
+
+pub(crate) fn brightness_devices() -> impl Iterator<Item = Result<BlockingDeviceImpl, SysError>> {
+    Context::new().devices()
+}
+
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum Value {
+    Actual,
+    /// Brightness that was last set, which may differ from the actual brightness.
+    Set,
+    Max,
+    Power,
+}
+
+impl Value {
+    /// Returns the name of the file holding the value. LEDs have no actual brightness distinct
+    /// from the brightness that was set.
+    pub(crate) fn file_name(&self, class: DeviceClass) -> &str {
+        match (self, class) {
+            (Value::Actual, DeviceClass::Backlight) => "actual_brightness",
+            (Value::Actual, DeviceClass::KeyboardLed | DeviceClass::Led) | (Value::Set, _) => {
+                "brightness"
+            }
+            (Value::Max, _) => "max_brightness",
+            (Value::Power, _) => "bl_power",
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub(crate) enum SysError {
+    #[error("Failed to read {} directory", .path.display())]
+    ReadingBacklightDir { path: PathBuf, source: io::Error },
+    #[error("Failed to read backlight device info {}", .path.display())]
+    ReadingBacklightDevice {
+        device: String,
+        path: PathBuf,
+        source: io::Error,
+    },
+    #[error("Failed to parse backlight info in {}: {reason}", .path.display())]
+    ParsingBacklightInfo {
+        device: String,
+        path: PathBuf,
+        reason: String,
+    },
+    #[error("Failed to write brightness to {}", .path.display())]
+    WritingBrightness {
+        device: String,
+        path: PathBuf,
+        source: io::Error,
+    },
+    #[error("Failed to set brightness of {device} through logind")]
+    SettingBrightnessThroughLogind {
+        device: String,
+        source: Box<zbus::Error>,
+    },
+    #[error("{}, and falling back failed", describe(first))]
+    FallingBack {
+        device: String,
+        first: Box<SysError>,
+        source: Box<SysError>,
+    },
+    #[error("Failed to write backlight power to {}", .path.display())]
+    WritingPower {
+        device: String,
+        path: PathBuf,
+        source: io::Error,
+    },
+    #[error("Failed to get VCP feature {code:#04x} of monitor {device} over DDC/CI")]
+    GettingVcpFeature {
+        device: String,
+        code: u8,
+        source: DdcError,
+    },
+    #[error("Failed to set luminance of monitor {device} over DDC/CI")]
+    SettingLuminance { device: String, source: DdcError },
+    #[error("Failed to set VCP feature {code:#04x} of monitor {device} over DDC/CI")]
+    SettingVcpFeature {
+        device: String,
+        code: u8,
+        source: DdcError,
+    },
+    #[error("Failed to get capabilities of monitor {device} over DDC/CI")]
+    GettingCapabilities { device: String, source: DdcError },
+    #[error("Device {device} is not controlled through DDC/CI")]
+    DdcUnsupported { device: String },
+    #[error("Monitor {device} can't be watched for brightness changes")]
+    WatchingUnsupported { device: String },
+    #[error("Device {device} has no backlight power control")]
+    PowerUnsupported { device: String },
+}
+
+impl SysError {
+    /// Returns the error of a fallback write method, keeping this error of the first method.
+    pub(crate) fn falling_back(self, device: &str, source: SysError) -> SysError {
+        SysError::FallingBack {
+            device: device.into(),
+            first: self.into(),
+            source: source.into(),
+        }
+    }
+
+    /// Returns whether the error means that a write method can't be used, rather than that it
+    /// failed this time.
+    pub(crate) fn is_unsupported_write(&self) -> bool {
+        match self {
+            SysError::WritingBrightness { source, .. } => matches!(
+                source.kind(),
+                io::ErrorKind::PermissionDenied | io::ErrorKind::ReadOnlyFilesystem
+            ),
+            SysError::SettingBrightnessThroughLogind { source, .. } => match &**source {
+                zbus::Error::MethodError(name, ..) => UNSUPPORTED_ERRORS.contains(&name.as_str()),
+                _ => false,
+            },
+            _ => false,
+        }
+    }
+}
+
+/// Returns the message of an error followed by those of its causes.
+fn describe(error: &(dyn StdError + 'static)) -> String {
+    successors(Some(error), |&e| e.source())
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(": ")
+}
+
+impl From<SysError> for Error {
+    fn from(e: SysError) -> Self {
+        match &e {
+            SysError::ReadingBacklightDir { .. } => Error::ListingDevices(e.into()),
+            SysError::ReadingBacklightDevice { device, .. }
+            | SysError::ParsingBacklightInfo { device, .. }
+            | SysError::GettingVcpFeature { device, .. }
+            | SysError::GettingCapabilities { device, .. }
+            | SysError::DdcUnsupported { device }
+            | SysError::WatchingUnsupported { device }
+            | SysError::PowerUnsupported { device } => Error::GettingDeviceInfo {
+                device: device.clone(),
+                source: e.into(),
+            },
+            SysError::WritingBrightness { device, .. }
+            | SysError::SettingBrightnessThroughLogind { device, .. }
+            | SysError::FallingBack { device, .. }
+            | SysError::WritingPower { device, .. }
+            | SysError::SettingLuminance { device, .. } => Error::SettingBrightness {
+                device: device.clone(),
+                source: e.into(),
+            },
+            SysError::SettingVcpFeature { device, code, .. } => Error::SettingVcpFeature {
+                device: device.clone(),
+                code: *code,
+                source: e.into(),
+            },
+        }
+    }
+}
+
+/// Monitor controlled through DDC/CI over an I2C bus.
+#[derive(Debug)]
+pub(crate) struct DdcDevice {
+    pub(crate) context: Context,
+    bus: u32,
+    pub(crate) connector: Connector,
+    ddc: Mutex<Ddc<I2cDevice>>,
+    capabilities: OnceLock<String>,
+}
+
+impl DdcDevice {
+    /// Returns the monitor on the given bus of a connector if it supports DDC/CI.
+    fn probe(context: &Context, bus: u32, connector: Connector) -> Option<Self> {
+        let edid = connector.edid().unwrap_or_default();
+        let mut probes = DDC_PROBES.lock().unwrap_or_else(PoisonError::into_inner);
+        let now = Instant::now();
+        let known = probes.get(bus, &edid, now);
+        if known == Some(false) {
+            return None;
+        }
+        let device = Self {
+            context: context.clone(),
+            bus,
+            connector,
+            ddc: Mutex::new(Ddc::new(I2cDevice::open(bus).ok()?)),
+            capabilities: OnceLock::new(),
+        };
+        if known.is_none() {
+            let supported = device.luminance().is_ok();
+            probes.insert(bus, edid, supported, now);
+            if !supported {
+                return None;
+            }
+        }
+        Some(device)
+    }
+
+    pub(crate) fn name(&self) -> String {
+        format!("i2c-{}", self.bus)
+    }
+
+    /// Returns the error for watching the monitor, which doesn't notify brightness changes and
+    /// is too slow to poll.
+    pub(crate) fn watching_unsupported(&self) -> SysError {
+        SysError::WatchingUnsupported {
+            device: self.name(),
+        }
+    }
+
+    pub(crate) fn edid(&self) -> Option<Vec<u8>> {
+        self.connector.edid()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Ddc<I2cDevice>> {
+        self.ddc.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub(crate) fn get_vcp(&self, code: u8) -> Result<VcpValue, SysError> {
+        self.lock()
+            .get_vcp(code)
+            .map_err(|source| SysError::GettingVcpFeature {
+                device: self.name(),
+                code,
+                source,
+            })
+    }
+
+    pub(crate) fn set_vcp(&self, code: u8, value: u16) -> Result<(), SysError> {
+        self.lock()
+            .set_vcp(code, value)
+            .map_err(|source| SysError::SettingVcpFeature {
+                device: self.name(),
+                code,
+                source,
+            })
+    }
+
+    /// Returns the capabilities string, read once as it takes seconds.
+    pub(crate) fn capabilities_string(&self) -> Result<String, SysError> {
+        if let Some(capabilities) = self.capabilities.get() {
+            return Ok(capabilities.clone());
+        }
+        let capabilities =
+            self.lock()
+                .capabilities()
+                .map_err(|source| SysError::GettingCapabilities {
+                    device: self.name(),
+                    source,
+                })?;
+        Ok(self.capabilities.get_or_init(|| capabilities).clone())
+    }
+
+    fn luminance(&self) -> Result<VcpValue, SysError> {
+        self.get_vcp(vcp::LUMINANCE)
+    }
+
+    fn set_luminance(&self, value: u16) -> Result<(), SysError> {
+        self.lock()
+            .set_vcp(vcp::LUMINANCE, value)
+            .map_err(|source| SysError::SettingLuminance {
+                device: self.name(),
+                source,
+            })
+    }
+}
+
+impl crate::blocking::Brightness for DdcDevice {
+    fn device_name(&self) -> Result<String, Error> {
+        Ok(self.name())
+    }
+
+    fn get(&self) -> Result<u32, Error> {
+        let value = self.luminance()?;
+        Ok(raw_to_percentage(value.current.into(), value.max.into()))
+    }
+
+    fn set(&self, percentage: u32) -> Result<(), Error> {
+        let max = self.luminance()?.max;
+        let value = percentage_to_raw(percentage, max.into());
+        Ok(self.set_luminance(value.min(max.into()) as u16)?)
+    }
+
+    fn get_raw(&self) -> Result<u32, Error> {
+        Ok(self.luminance()?.current.into())
+    }
+
+    fn max_raw(&self) -> Result<u32, Error> {
+        Ok(self.luminance()?.max.into())
+    }
+
+    fn set_raw(&self, value: u32) -> Result<(), Error> {
+        let max = self.luminance()?.max;
+        Ok(self.set_luminance(value.min(max.into()) as u16)?)
+    }
+}
+
+/// Results of probing monitors for DDC/CI support, by bus and EDID.
+#[derive(Debug)]
+struct DdcProbes(Vec<DdcProbe>);
+
+#[derive(Debug)]
+struct DdcProbe {
+    bus: u32,
+    edid: Vec<u8>,
+    supported: bool,
+    time: Instant,
+}
+
+impl DdcProbes {
+    /// Returns whether the monitor with the given EDID on a bus was found to support DDC/CI.
+    ///
+    /// Results for other monitors on the bus, which was replugged, are forgotten, as well as
+    /// failed probes older than [`DDC_PROBE_RETRY`].
+    fn get(&mut self, bus: u32, edid: &[u8], now: Instant) -> Option<bool> {
+        self.0.retain(|p| {
+            p.bus != bus
+                || (p.edid == edid && (p.supported || now.duration_since(p.time) < DDC_PROBE_RETRY))
+        });
+        self.0.iter().find(|p| p.bus == bus).map(|p| p.supported)
+    }
+
+    fn insert(&mut self, bus: u32, edid: Vec<u8>, supported: bool, now: Instant) {
+        self.0.retain(|p| p.bus != bus);
+        self.0.push(DdcProbe {
+            bus,
+            edid,
+            supported,
+            time: now,
+        });
+    }
+}
+
+/// I2C bus device node, addressing the DDC/CI interface of the monitor.
+#[derive(Debug)]
+struct I2cDevice {
+    file: File,
+}
+
+impl I2cDevice {
+    fn open(bus: u32) -> io::Result<Self> {
+        let file = File::options()
+            .read(true)
+            .write(true)
+            .open(format!("/dev/i2c-{bus}"))?;
+        // SAFETY: `I2C_SLAVE` expects the 7-bit address of the device as integer.
+        unsafe {
+            ioctl(
+                &file,
+                IntegerSetter::<I2C_SLAVE>::new_usize(DDC_ADDRESS.into()),
+            )
+        }?;
+        Ok(Self { file })
+    }
+}
+
+impl I2cTransport for I2cDevice {
+    fn write(&mut self, data: &[u8]) -> io::Result<()> {
+        self.file.write_all(data)
+    }
+
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<()> {
+        self.file.read_exact(buffer)
+    }
+}
+
+/// Connector of a DRM device, e.g. `card0-eDP-1`.
+#[derive(Clone, Debug)]
+pub(crate) struct Connector {
+    path: PathBuf,
+}
+
+impl Connector {
+    /// Returns the connectors of all DRM devices.
+    fn all(context: &Context) -> Vec<Connector> {
+        let Ok(entries) = fs::read_dir(context.sysfs_root.join(DRM_CLASS)) else {
+            return Vec::new();
+        };
+        entries
+            .filter_map(Result::ok)
+            .filter(|entry| {
+                let name = entry.file_name();
+                let name = name.to_string_lossy();
+                name.starts_with("card") && name.contains('-')
+            })
+            .map(|entry| Connector { path: entry.path() })
+            .collect()
+    }
+
+    /// Returns the connector type and index, e.g. `eDP-1`.
+    pub(crate) fn kind(&self) -> String {
+        let name = self.path.file_name().unwrap_or_default().to_string_lossy();
+        name.split_once('-')
+            .map_or_else(String::new, |(_, kind)| kind.into())
+    }
+
+    /// Returns whether the connector is for a built-in panel.
+    pub(crate) fn is_internal(&self) -> bool {
+        let kind = self.kind();
+        INTERNAL_CONNECTORS.iter().any(|c| kind.starts_with(c))
+    }
+
+    /// Returns the sysfs path of the connector under its graphics card, without card numbers.
+    pub(crate) fn stable_path(&self) -> String {
+        without_card_numbers(fs::canonicalize(&self.path).as_ref().unwrap_or(&self.path))
+    }
+
+    fn is_connected(&self) -> bool {
+        fs::read_to_string(self.path.join("status")).is_ok_and(|s| s.trim() == "connected")
+    }
+
+    /// Returns the EDID of the connected display, if any.
+    fn edid(&self) -> Option<Vec<u8>> {
+        fs::read(self.path.join("edid"))
+            .ok()
+            .filter(|edid| !edid.is_empty())
+    }
+
+    /// Returns the number of the I2C bus of the connector.
+    ///
+    /// Most drivers link the bus as `ddc`, while others create it as a child of the connector.
+    fn bus(&self) -> Option<u32> {
+        match fs::read_link(self.path.join("ddc")) {
+            Ok(target) => i2c_bus_number(target.file_name()?),
+            Err(_) => fs::read_dir(&self.path)
+                .ok()?
+                .filter_map(Result::ok)
+                .find_map(|entry| i2c_bus_number(&entry.file_name())),
+        }
+    }
+
+    /// Returns the connector of the display controlled by a backlight device.
+    ///
+    /// Some drivers register backlight devices under the connector. Backlight devices of ddcci
+    /// monitors are under the I2C bus of the connector. Otherwise, the connected built-in panel of
+    /// the graphics card the device belongs to is used, or of any graphics card for firmware and
+    /// platform devices, provided there is only one.
+    fn of_backlight(context: &Context, device: &str) -> Option<Connector> {
+        let parent = fs::canonicalize(
+            context
+                .device_dir(DeviceClass::Backlight, device)
+                .join("device"),
+        )
+        .ok();
+        if let Some(parent) = &parent
+            && parent.join("edid").exists()
+        {
+            return Some(Connector {
+                path: parent.clone(),
+            });
+        }
+        let connectors = Connector::all(context);
+        let bus = parent
+            .iter()
+            .flat_map(|p| p.ancestors())
+            .find_map(|p| i2c_bus_number(p.file_name()?));
+        if let Some(bus) = bus
+            && let Some(connector) = connectors.iter().find(|c| c.bus() == Some(bus))
+        {
+            return Some(connector.clone());
+        }
+        let card = match read_parent(context, device) {
+            Some(Parent::Pci {
+                path,
+                display: true,
+            }) => Some(path),
+            _ => None,
+        };
+        let mut panels = connectors.into_iter().filter(|c| {
+            c.is_internal()
+                && c.is_connected()
+                && card.as_ref().is_none_or(|card| {
+                    fs::canonicalize(&c.path).is_ok_and(|path| path.starts_with(card))
+                })
+        });
+        let panel = panels.next()?;
+        panels.next().is_none().then_some(panel)
+    }
+}
+
+/// Returns the driver of a device from its name, e.g. `acpi_video` for `acpi_video0` or `i2c` for
+/// `i2c-5`.
+pub(crate) fn driver(device: &str) -> String {
+    match device.trim_end_matches(|c: char| c.is_ascii_digit() || c == '-') {
+        "" => device.into(),
+        driver => driver.into(),
+    }
+}
+
+fn i2c_bus_number(name: &std::ffi::OsStr) -> Option<u32> {
+    name.to_str()?.strip_prefix("i2c-")?.parse().ok()
+}
+
+/// Waits for changes of a backlight device attribute.
+///
+/// sysfs notifies changes of some attributes through `POLLPRI`, e.g. `actual_brightness` when
+/// brightness is changed with hotkeys. Files that don't support notifications never report events,
+/// so the wait timeout doubles as polling interval.
+#[derive(Debug)]
+pub(crate) struct AttributeWatcher {
+    device: String,
+    path: PathBuf,
+    file: File,
+}
+
+impl AttributeWatcher {
+    pub(crate) fn new(
+        context: &Context,
+        class: DeviceClass,
+        device: &str,
+        name: Value,
+    ) -> Result<Self, SysError> {
+        let path = context
+            .device_dir(class, device)
+            .join(name.file_name(class));
+        let file = File::open(&path).map_err(|source| SysError::ReadingBacklightDevice {
+            device: device.into(),
+            path: path.clone(),
+            source,
+        })?;
+        Ok(Self {
+            device: device.into(),
+            path,
+            file,
+        })
+    }
+
+    /// Waits until the attribute changes or the timeout expires.
+    pub(crate) fn wait(&mut self, timeout: Duration) -> Result<(), SysError> {
+        // sysfs only notifies again once the attribute has been read.
+        self.file
+            .rewind()
+            .and_then(|_| self.file.read_to_end(&mut Vec::new()))
+            .map_err(|source| self.reading_error(source))?;
+        let timeout = Timespec::try_from(timeout).ok();
+        let mut fds = [PollFd::new(&self.file, PollFlags::PRI | PollFlags::ERR)];
+        match poll(&mut fds, timeout.as_ref()) {
+            Ok(_) | Err(Errno::INTR) => Ok(()),
+            Err(e) => Err(self.reading_error(e.into())),
+        }
+    }
+
+    fn reading_error(&self, source: io::Error) -> SysError {
+        SysError::ReadingBacklightDevice {
+            device: self.device.clone(),
+            path: self.path.clone(),
+            source,
+        }
     }
 }
 
-#[derive(Clone, Copy, Debug)]
-pub(crate) enum Value {
-    Actual,
-    Max,
+/// Waits for backlight devices to appear or disappear.
+///
+/// Kernel uevents are only listened to for the real sysfs. Other sysfs roots are watched with
+/// inotify. Without any notification source, waiting simply sleeps.
+#[derive(Debug)]
+pub(crate) struct DirectoryWatcher {
+    fds: Vec<OwnedFd>,
+    /// Whether the last wait was ended by a notification.
+    notified: bool,
 }
 
-impl Value {
-    pub(crate) fn as_str(&self) -> &str {
-        match self {
-            Value::Actual => "actual_brightness",
-            Value::Max => "max_brightness",
+impl DirectoryWatcher {
+    pub(crate) fn new(context: &Context) -> Self {
+        let inotify = inotify::init(inotify::CreateFlags::CLOEXEC | inotify::CreateFlags::NONBLOCK)
+            .and_then(|fd| {
+                let flags = inotify::WatchFlags::CREATE
+                    | inotify::WatchFlags::DELETE
+                    | inotify::WatchFlags::MOVED_FROM
+                    | inotify::WatchFlags::MOVED_TO;
+                for dir in [BACKLIGHT_CLASS, LEDS_CLASS] {
+                    if context.device_classes.iter().any(|c| c.dir() == dir) {
+                        inotify::add_watch(&fd, context.sysfs_root.join(dir), flags)?;
+                    }
+                }
+                Ok(fd)
+            });
+        let uevents = context.is_system().then(|| {
+            let fd = socket_with(
+                AddressFamily::NETLINK,
+                SocketType::DGRAM,
+                SocketFlags::CLOEXEC | SocketFlags::NONBLOCK,
+                Some(netlink::KOBJECT_UEVENT),
+            )?;
+            // Multicast group 1 receives kernel uevents.
+            bind(&fd, &SocketAddrNetlink::new(0, 1))?;
+            Ok::<_, Errno>(fd)
+        });
+        Self {
+            fds: inotify
+                .into_iter()
+                .chain(uevents.and_then(Result::ok))
+                .collect(),
+            notified: false,
         }
     }
-}
-
-#[derive(Debug, Error)]
-pub(crate) enum SysError {
-    #[error("Failed to read {} directory", BACKLIGHT_DIR)]
-    ReadingBacklightDir(#[source] io::Error),
-    #[error("Failed to read backlight device info {}", .path.display())]
-    ReadingBacklightDevice {
-        device: String,
-        path: PathBuf,
-        source: io::Error,
-    },
-    #[error("Failed to parse backlight info in {}: {reason}", .path.display())]
-    ParsingBacklightInfo {
-        device: String,
-        path: PathBuf,
-        reason: String,
-    },
-    #[error("Failed to write brightness to {}", .path.display())]
-    WritingBrightness {
-        device: String,
-        path: PathBuf,
-        source: io::Error,
-    },
-}
 
-impl From<SysError> for Error {
-    fn from(e: SysError) -> Self {
-        match &e {
-            SysError::ReadingBacklightDir(_) => Error::ListingDevices(e.into()),
-            SysError::ReadingBacklightDevice { device, .. }
-            | SysError::ParsingBacklightInfo { device, .. } => Error::GettingDeviceInfo {
-                device: device.clone(),
-                source: e.into(),
-            },
-            SysError::WritingBrightness { device, .. } => Error::SettingBrightness {
-                device: device.clone(),
-                source: e.into(),
-            },
+    /// Waits until a notification arrives or the timeout expires.
+    ///
+    /// After a notification, the next wait lasts at most [`RESCAN_DELAY`], as a device directory
+    /// may be created before the attributes that make it a device.
+    pub(crate) fn wait(&mut self, timeout: Duration) {
+        let timeout = match mem::take(&mut self.notified) {
+            true => timeout.min(RESCAN_DELAY),
+            false => timeout,
+        };
+        if self.fds.is_empty() {
+            thread::sleep(timeout);
+            return;
+        }
+        let timeout = Timespec::try_from(timeout).ok();
+        let mut fds = self
+            .fds
+            .iter()
+            .map(|fd| PollFd::new(fd, PollFlags::IN))
+            .collect::<Vec<_>>();
+        let _ = poll(&mut fds, timeout.as_ref());
+        // Notifications only indicate that devices must be enumerated again; discard them.
+        let mut buffer = [0; 4096];
+        for fd in &self.fds {
+            while matches!(rustix::io::read(fd, &mut buffer), Ok(n) if n > 0) {
+                self.notified = true;
+            }
         }
     }
 }
@@ -148,10 +1799,15 @@ impl From<SysError> for Error {
 ///
 /// Note: Even though this makes a call to `std::fs`, we are communicating with a kernel pseudo file
 /// system so it is safe to call from an async context.
-pub(crate) fn read_value(device: &str, name: Value) -> Result<u32, SysError> {
-    let path = [BACKLIGHT_DIR, device, name.as_str()]
-        .iter()
-        .collect::<PathBuf>();
+pub(crate) fn read_value(
+    context: &Context,
+    class: DeviceClass,
+    device: &str,
+    name: Value,
+) -> Result<u32, SysError> {
+    let path = context
+        .device_dir(class, device)
+        .join(name.file_name(class));
     fs::read_to_string(&path)
         .map_err(|source| SysError::ReadingBacklightDevice {
             device: device.into(),
@@ -167,13 +1823,65 @@ pub(crate) fn read_value(device: &str, name: Value) -> Result<u32, SysError> {
         })
 }
 
+/// Reads the scale of a backlight device from the filesystem. Devices without a readable scale
+/// (e.g. on kernels older than 5.4) have an unknown scale.
+pub(crate) fn read_scale(context: &Context, class: DeviceClass, device: &str) -> Scale {
+    let path = context.device_dir(class, device).join("scale");
+    match fs::read_to_string(path).as_deref().map(str::trim) {
+        Ok("linear") => Scale::Linear,
+        Ok("non-linear") => Scale::NonLinear,
+        _ => Scale::Unknown,
+    }
+}
+
+/// Reads the type of a backlight device from the filesystem.
+pub(crate) fn read_device_type(context: &Context, class: DeviceClass, device: &str) -> DeviceType {
+    let path = context.device_dir(class, device).join("type");
+    match fs::read_to_string(path).as_deref().map(str::trim) {
+        Ok("firmware") => DeviceType::Firmware,
+        Ok("platform") => DeviceType::Platform,
+        Ok("raw") => DeviceType::Raw,
+        _ => DeviceType::Unknown,
+    }
+}
+
+/// Finds the closest PCI or platform device a backlight device belongs to by walking up the device
+/// hierarchy.
+pub(crate) fn read_parent(context: &Context, device: &str) -> Option<Parent> {
+    let mut path = fs::canonicalize(
+        context
+            .device_dir(DeviceClass::Backlight, device)
+            .join("device"),
+    )
+    .ok()?;
+    loop {
+        let subsystem = fs::read_link(path.join("subsystem")).ok();
+        match subsystem.as_deref().and_then(Path::file_name) {
+            Some(s) if s == "pci" => {
+                // PCI class 0x03 is for display controllers.
+                let display = fs::read_to_string(path.join("class"))
+                    .is_ok_and(|class| class.trim().starts_with("0x03"));
+                return Some(Parent::Pci { path, display });
+            }
+            Some(s) if s == "platform" => return Some(Parent::Platform),
+            _ => {}
+        }
+        if !path.pop() {
+            return None;
+        }
+    }
+}
+
 /// Sets the brightness for a backlight device via the filesystem.
 ///
 /// This is a blocking operation that can take approximately 10-100ms depending on the device.
-pub(crate) fn set_value(device: &str, value: u32) -> Result<(), SysError> {
-    let path = [BACKLIGHT_DIR, device, "brightness"]
-        .iter()
-        .collect::<PathBuf>();
+pub(crate) fn set_value(
+    context: &Context,
+    class: DeviceClass,
+    device: &str,
+    value: u32,
+) -> Result<(), SysError> {
+    let path = context.device_dir(class, device).join("brightness");
     fs::write(&path, value.to_string()).map_err(|source| SysError::WritingBrightness {
         device: device.into(),
         path: path.clone(),
@@ -181,3 +1889,939 @@ pub(crate) fn set_value(device: &str, value: u32) -> Result<(), SysError> {
     })?;
     Ok(())
 }
+
+/// Sets the `bl_power` value of a backlight device via the filesystem.
+fn write_power(context: &Context, device: &str, value: u32) -> Result<(), SysError> {
+    let path = context
+        .device_dir(DeviceClass::Backlight, device)
+        .join(Value::Power.file_name(DeviceClass::Backlight));
+    fs::write(&path, value.to_string()).map_err(|source| SysError::WritingPower {
+        device: device.into(),
+        path: path.clone(),
+        source,
+    })
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::{
+        DeviceEvent,
+        blocking::{self, Brightness},
+    };
+    use std::{
+        os::unix::net::{UnixListener, UnixStream},
+        sync::{
+            Arc,
+            atomic::{AtomicUsize, Ordering},
+        },
+        thread,
+    };
+    use zbus::Guid;
+
+    /// Serializes tests that replace the shared system bus connection.
+    static SYSTEM_BUS_TESTS: Mutex<()> = Mutex::new(());
+
+    /// Returns a path in the temporary directory that no other test uses.
+    fn temp_path(kind: &str) -> PathBuf {
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+        std::env::temp_dir().join(format!(
+            "brightness-{kind}-{}-{}",
+            std::process::id(),
+            COUNT.fetch_add(1, Ordering::Relaxed)
+        ))
+    }
+
+    /// Fake sysfs root, removed when dropped.
+    pub(crate) struct FakeSysfs(PathBuf);
+
+    impl FakeSysfs {
+        pub(crate) fn new() -> Self {
+            let root = temp_path("sysfs");
+            fs::create_dir_all(root.join(BACKLIGHT_CLASS)).unwrap();
+            Self(root)
+        }
+
+        pub(crate) fn device_dir(&self, name: &str) -> PathBuf {
+            self.0.join(BACKLIGHT_CLASS).join(name)
+        }
+
+        pub(crate) fn add_device(&self, name: &str, max: u32, actual: u32) {
+            let dir = self.device_dir(name);
+            fs::create_dir_all(&dir).unwrap();
+            write_attributes(&dir, max, actual);
+        }
+
+        pub(crate) fn context(&self) -> Context {
+            Context::with_sysfs_root(&self.0)
+        }
+
+        /// Adds an LED, which has no `actual_brightness`.
+        pub(crate) fn add_led(&self, name: &str, max: u32, brightness: u32) {
+            let dir = self.0.join(LEDS_CLASS).join(name);
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(dir.join("max_brightness"), max.to_string()).unwrap();
+            fs::write(dir.join("brightness"), brightness.to_string()).unwrap();
+        }
+
+        /// Adds a PCI graphics card with a DRM device, returning the path of the PCI device.
+        pub(crate) fn add_card(&self, pci: &str, card: &str) -> PathBuf {
+            let bus = self.0.join("bus/pci");
+            let dir = self.0.join("devices/pci0000:00").join(pci);
+            fs::create_dir_all(&bus).unwrap();
+            fs::create_dir_all(dir.join("drm").join(card)).unwrap();
+            std::os::unix::fs::symlink(&bus, dir.join("subsystem")).unwrap();
+            fs::write(dir.join("class"), "0x030000").unwrap();
+            dir
+        }
+
+        /// Adds a DRM connector of a card added with [`Self::add_card`], with its I2C bus, and
+        /// returns its path.
+        pub(crate) fn add_connector(&self, card: &Path, name: &str, bus: u32) -> PathBuf {
+            let card_name = name.split_once('-').unwrap().0;
+            let dir = card.join("drm").join(card_name).join(name);
+            fs::create_dir_all(dir.join(format!("i2c-{bus}"))).unwrap();
+            fs::write(dir.join("status"), "connected").unwrap();
+            fs::write(dir.join("edid"), []).unwrap();
+            let class = self.0.join(DRM_CLASS);
+            fs::create_dir_all(&class).unwrap();
+            std::os::unix::fs::symlink(&dir, class.join(name)).unwrap();
+            dir
+        }
+
+        /// Adds a backlight device registered under `parent`, as the class directory links to.
+        pub(crate) fn add_sibling_device(&self, parent: &Path, name: &str) {
+            let dir = parent.join("backlight").join(name);
+            fs::create_dir_all(&dir).unwrap();
+            write_attributes(&dir, 255, 128);
+            std::os::unix::fs::symlink(parent, dir.join("device")).unwrap();
+            std::os::unix::fs::symlink(&dir, self.device_dir(name)).unwrap();
+        }
+
+        /// Makes `parent` the device a backlight device belongs to.
+        pub(crate) fn set_parent(&self, name: &str, parent: &Path) {
+            std::os::unix::fs::symlink(parent, self.device_dir(name).join("device")).unwrap();
+        }
+    }
+
+    impl Drop for FakeSysfs {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn write_attributes(dir: &Path, max: u32, actual: u32) {
+        fs::write(dir.join("max_brightness"), max.to_string()).unwrap();
+        fs::write(dir.join("actual_brightness"), actual.to_string()).unwrap();
+    }
+
+    #[test]
+    fn watching_follows_actual_brightness() {
+        let sysfs = FakeSysfs::new();
+        sysfs.add_device("panel", 200, 80);
+        let device = sysfs
+            .context()
+            .blocking_brightness_devices()
+            .next()
+            .unwrap()
+            .unwrap();
+        let mut changes = device.watch();
+        assert_eq!(changes.next().unwrap().unwrap(), 40);
+
+        let writer = thread::spawn({
+            let dir = sysfs.device_dir("panel");
+            move || {
+                thread::sleep(Duration::from_millis(50));
+                fs::write(dir.join("actual_brightness"), "150").unwrap();
+            }
+        });
+        assert_eq!(changes.next().unwrap().unwrap(), 75);
+        writer.join().unwrap();
+    }
+
+    type Event = Result<DeviceEvent<blocking::BrightnessDevice>, Error>;
+
+    fn added_name(event: Option<Event>) -> String {
+        match event {
+            Some(Ok(DeviceEvent::Added(device))) => device.device_name().unwrap(),
+            other => panic!("expected an added device, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn device_events_follow_the_class_directory() {
+        let sysfs = FakeSysfs::new();
+        sysfs.add_device("panel", 100, 40);
+        let mut events = sysfs.context().blocking_device_events();
+        assert_eq!(added_name(events.next()), "panel");
+
+        let dir = sysfs.device_dir("external");
+        let creator = thread::spawn({
+            let dir = dir.clone();
+            move || {
+                thread::sleep(Duration::from_millis(50));
+                fs::create_dir(&dir).unwrap();
+                // The directory alone is not a device yet.
+                thread::sleep(Duration::from_millis(20));
+                write_attributes(&dir, 255, 255);
+            }
+        });
+        assert_eq!(added_name(events.next()), "external");
+        creator.join().unwrap();
+
+        fs::remove_dir_all(&dir).unwrap();
+        match events.next() {
+            Some(Ok(DeviceEvent::Removed(name))) => assert_eq!(name, "external"),
+            other => panic!("expected a removed device, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn directory_changes_shorten_the_next_wait() {
+        let sysfs = FakeSysfs::new();
+        let mut watcher = DirectoryWatcher::new(&sysfs.context());
+        assert!(!watcher.notified);
+
+        fs::create_dir(sysfs.device_dir("external")).unwrap();
+        watcher.wait(Duration::MAX);
+        assert!(watcher.notified);
+        // The flag only applies to the wait right after the notification.
+        watcher.wait(Duration::ZERO);
+        assert!(!watcher.notified);
+    }
+
+    /// Behavior of the session of the calling process in a [`FakeLogind`].
+    #[derive(Clone, Copy, Default)]
+    pub(crate) enum AutoSession {
+        #[default]
+        Working,
+        /// Lacks `SetBrightness`, like before systemd 243.
+        Outdated,
+        /// Fails to set brightness.
+        Failing,
+        /// Doesn't exist, like for processes outside of any session.
+        Missing,
+    }
+
+    /// Object path of session `2`, the active session of `seat0` in a [`FakeLogind`].
+    pub(crate) const SEAT_SESSION_PATH: &str = "/org/freedesktop/login1/session/_32";
+    /// Object path of session `c3`, the session of [`SESSION_PID`] in a [`FakeLogind`].
+    pub(crate) const PID_SESSION_PATH: &str = "/org/freedesktop/login1/session/c3";
+    /// PID of a process in session `c3` of a [`FakeLogind`].
+    pub(crate) const SESSION_PID: u32 = 4242;
+
+    /// Brightness change received by a session: the session path, subsystem, device name and
+    /// value.
+    type Call = (&'static str, String, String, u32);
+
+    /// Fake logind served over peer-to-peer connections, recording brightness changes.
+    ///
+    /// Besides the session of the calling process, it has working sessions `2`, active on
+    /// `seat0`, and `c3`.
+    #[derive(Clone, Default)]
+    pub(crate) struct FakeLogind {
+        auto_session: AutoSession,
+        calls: Arc<Mutex<Vec<Call>>>,
+        connections: Arc<Mutex<Vec<zbus::blocking::Connection>>>,
+        clients: Arc<AtomicUsize>,
+    }
+
+    impl FakeLogind {
+        pub(crate) fn outdated() -> Self {
+            Self::with_auto_session(AutoSession::Outdated)
+        }
+
+        pub(crate) fn with_auto_session(auto_session: AutoSession) -> Self {
+            Self {
+                auto_session,
+                ..Self::default()
+            }
+        }
+
+        /// Returns a peer-to-peer connection to the fake.
+        pub(crate) fn connect(&self) -> zbus::Connection {
+            let (client, server) = UnixStream::pair().unwrap();
+            let serving = thread::spawn({
+                let fake = self.clone();
+                move || fake.serve(server)
+            });
+            let client = zbus::blocking::connection::Builder::async_io_unix_stream(client)
+                .p2p()
+                .build()
+                .unwrap();
+            serving.join().unwrap();
+            client.into_inner()
+        }
+
+        /// Serves the fake as the system bus until the returned guard is dropped.
+        pub(crate) fn serve_system_bus(&self) -> SystemBusGuard {
+            let lock = SYSTEM_BUS_TESTS
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+            let socket = temp_path("bus");
+            let listener = UnixListener::bind(&socket).unwrap();
+            thread::spawn({
+                let fake = self.clone();
+                move || listener.incoming().for_each(|s| fake.serve(s.unwrap()))
+            });
+            *lock_system_bus() = None;
+            lock_logind_unsupported().clear();
+            SystemBusGuard {
+                _lock: lock,
+                socket,
+            }
+        }
+
+        fn serve(&self, stream: UnixStream) {
+            let builder = zbus::blocking::connection::Builder::async_io_unix_stream(stream)
+                .server(Guid::generate())
+                .unwrap()
+                .p2p()
+                .serve_at("/org/freedesktop/DBus", FakeBus(self.clients.clone()))
+                .unwrap()
+                .serve_at(MANAGER_OBJECT_PATH, FakeManager)
+                .unwrap()
+                .serve_at(FakeManager::SEAT_PATH, FakeSeat)
+                .unwrap()
+                .serve_at(SEAT_SESSION_PATH, self.session(SEAT_SESSION_PATH))
+                .unwrap()
+                .serve_at(PID_SESSION_PATH, self.session(PID_SESSION_PATH))
+                .unwrap();
+            let builder = match self.auto_session {
+                AutoSession::Working => {
+                    builder.serve_at(SESSION_OBJECT_PATH, self.session(SESSION_OBJECT_PATH))
+                }
+                AutoSession::Outdated => builder.serve_at(SESSION_OBJECT_PATH, OutdatedSession),
+                AutoSession::Failing => builder.serve_at(SESSION_OBJECT_PATH, FailingSession),
+                AutoSession::Missing => Ok(builder),
+            };
+            let connection = builder.unwrap().build().unwrap();
+            self.connections.lock().unwrap().push(connection);
+        }
+
+        /// Closes all connections to the fake.
+        pub(crate) fn disconnect(&self) {
+            self.connections.lock().unwrap().clear();
+        }
+
+        fn session(&self, path: &'static str) -> FakeSession {
+            FakeSession {
+                path,
+                calls: self.calls.clone(),
+            }
+        }
+
+        /// Returns the subsystem, device name and value of each `SetBrightness` call received by
+        /// the session of the calling process.
+        pub(crate) fn calls(&self) -> Vec<(String, String, u32)> {
+            self.calls_to(SESSION_OBJECT_PATH)
+        }
+
+        /// Returns the subsystem, device name and value of each `SetBrightness` call received by
+        /// the session at the given path.
+        pub(crate) fn calls_to(&self, path: &str) -> Vec<(String, String, u32)> {
+            let calls = self.calls.lock().unwrap();
+            calls
+                .iter()
+                .filter(|call| call.0 == path)
+                .map(|(_, subsystem, name, value)| (subsystem.clone(), name.clone(), *value))
+                .collect()
+        }
+    }
+
+    /// Keeps a fake logind as the system bus.
+    pub(crate) struct SystemBusGuard {
+        _lock: MutexGuard<'static, ()>,
+        socket: PathBuf,
+    }
+
+    impl SystemBusGuard {
+        /// Returns a context for the system sysfs root connecting to the fake system bus.
+        pub(crate) fn context(&self) -> Context {
+            Context {
+                system_bus_address: Some(format!("unix:path={}", self.socket.display())),
+                ..Context::new()
+            }
+        }
+    }
+
+    impl Drop for SystemBusGuard {
+        fn drop(&mut self) {
+            *lock_system_bus() = None;
+            lock_logind_unsupported().clear();
+            let _ = fs::remove_file(&self.socket);
+        }
+    }
+
+    /// Answers the `Hello` call made by clients connecting to a bus.
+    struct FakeBus(Arc<AtomicUsize>);
+
+    #[zbus::interface(name = "org.freedesktop.DBus")]
+    impl FakeBus {
+        fn hello(&self) -> String {
+            format!(":1.{}", self.0.fetch_add(1, Ordering::Relaxed))
+        }
+    }
+
+    struct FakeSession {
+        path: &'static str,
+        calls: Arc<Mutex<Vec<Call>>>,
+    }
+
+    #[zbus::interface(name = "org.freedesktop.login1.Session")]
+    impl FakeSession {
+        fn set_brightness(&self, subsystem: String, name: String, value: u32) {
+            let mut calls = self.calls.lock().unwrap();
+            calls.push((self.path, subsystem, name, value));
+        }
+    }
+
+    struct OutdatedSession;
+
+    #[zbus::interface(name = "org.freedesktop.login1.Session")]
+    impl OutdatedSession {
+        fn activate(&self) {}
+    }
+
+    struct FailingSession;
+
+    #[zbus::interface(name = "org.freedesktop.login1.Session")]
+    impl FailingSession {
+        fn set_brightness(
+            &self,
+            _subsystem: String,
+            _name: String,
+            _value: u32,
+        ) -> zbus::fdo::Result<()> {
+            Err(zbus::fdo::Error::Failed("Device busy".into()))
+        }
+    }
+
+    struct FakeManager;
+
+    impl FakeManager {
+        const SEAT_PATH: &str = "/org/freedesktop/login1/seat/seat0";
+    }
+
+    #[zbus::interface(name = "org.freedesktop.login1.Manager")]
+    impl FakeManager {
+        fn get_seat(&self, id: String) -> zbus::fdo::Result<OwnedObjectPath> {
+            match id.as_str() {
+                "seat0" => Ok(ObjectPath::from_static_str_unchecked(Self::SEAT_PATH).into()),
+                _ => Err(zbus::fdo::Error::Failed(format!("No seat '{id}' known"))),
+            }
+        }
+
+        fn get_session(&self, id: String) -> zbus::fdo::Result<OwnedObjectPath> {
+            let path = match id.as_str() {
+                "2" => SEAT_SESSION_PATH,
+                "c3" => PID_SESSION_PATH,
+                _ => return Err(zbus::fdo::Error::Failed(format!("No session '{id}' known"))),
+            };
+            Ok(ObjectPath::from_static_str_unchecked(path).into())
+        }
+
+        #[zbus(name = "GetSessionByPID")]
+        fn get_session_by_pid(&self, pid: u32) -> zbus::fdo::Result<OwnedObjectPath> {
+            match pid {
+                SESSION_PID => Ok(ObjectPath::from_static_str_unchecked(PID_SESSION_PATH).into()),
+                _ => Err(zbus::fdo::Error::Failed(format!(
+                    "PID {pid} does not belong to any known session"
+                ))),
+            }
+        }
+    }
+
+    struct FakeSeat;
+
+    #[zbus::interface(name = "org.freedesktop.login1.Seat")]
+    impl FakeSeat {
+        #[zbus(property)]
+        fn active_session(&self) -> (String, OwnedObjectPath) {
+            let path = ObjectPath::from_static_str_unchecked(SEAT_SESSION_PATH);
+            ("2".into(), path.into())
+        }
+    }
+
+    #[test]
+    fn brightness_is_set_through_logind() {
+        let sysfs = FakeSysfs::new();
+        sysfs.add_device("panel", 200, 50);
+        let logind = FakeLogind::default();
+        let mut context = sysfs.context();
+        context.set_dbus_connection(logind.connect());
+        let device = context
+            .blocking_brightness_devices()
+            .next()
+            .unwrap()
+            .unwrap();
+        device.set(40).unwrap();
+        assert_eq!(logind.calls(), [("backlight".into(), "panel".into(), 80)]);
+        assert_eq!(device.write_method().unwrap(), WriteMethod::Logind);
+        assert!(!sysfs.device_dir("panel").join("brightness").exists());
+    }
+
+    #[test]
+    fn leds_are_set_through_logind_in_their_subsystem() {
+        let sysfs = FakeSysfs::new();
+        sysfs.add_led("tpacpi::kbd_backlight", 2, 0);
+        let logind = FakeLogind::default();
+        let mut context = sysfs.context();
+        context.set_dbus_connection(logind.connect());
+        context.set_device_classes([DeviceClass::KeyboardLed]);
+        let device = context
+            .blocking_brightness_devices()
+            .next()
+            .unwrap()
+            .unwrap();
+        device.set(100).unwrap();
+        let expected = [("leds".into(), "tpacpi::kbd_backlight".into(), 2)];
+        assert_eq!(logind.calls(), expected);
+    }
+
+    #[test]
+    fn sysfs_is_used_when_logind_cannot_set_brightness() {
+        let sysfs = FakeSysfs::new();
+        sysfs.add_device("panel", 200, 50);
+        let logind = FakeLogind::outdated();
+        let mut context = sysfs.context();
+        context.set_dbus_connection(logind.connect());
+        let device = context
+            .blocking_brightness_devices()
+            .next()
+            .unwrap()
+            .unwrap();
+        device.set(40).unwrap();
+        let written = fs::read_to_string(sysfs.device_dir("panel").join("brightness")).unwrap();
+        assert_eq!(written, "80");
+        assert_eq!(device.write_method().unwrap(), WriteMethod::Sysfs);
+    }
+
+    #[test]
+    fn lost_system_bus_connection_is_replaced() {
+        let logind = FakeLogind::default();
+        let bus = logind.serve_system_bus();
+        let context = bus.context();
+        context
+            .set_brightness(DeviceClass::Backlight, "panel", 1)
+            .unwrap();
+        let first = cached_system_bus().unwrap();
+        logind.disconnect();
+        context
+            .set_brightness(DeviceClass::Backlight, "panel", 2)
+            .unwrap();
+        let second = cached_system_bus().unwrap();
+        assert_ne!(first.unique_name(), second.unique_name());
+        let values = logind
+            .calls()
+            .into_iter()
+            .map(|(.., v)| v)
+            .collect::<Vec<_>>();
+        assert_eq!(values, [1, 2]);
+    }
+
+    #[test]
+    fn unsupported_logind_is_remembered() {
+        let bus = FakeLogind::outdated().serve_system_bus();
+        let context = bus.context();
+        let result = context.set_brightness(DeviceClass::Backlight, "panel", 1);
+        let error = context.check_logind("panel", result).unwrap_err();
+        assert!(error.is_unsupported_write(), "{error:?}");
+        // No device tries logind anymore.
+        assert_eq!(
+            context.write_plan(&OnceLock::new()),
+            (WriteMethod::Sysfs, None)
+        );
+    }
+
+    #[test]
+    fn missing_session_is_not_remembered_as_unsupported() {
+        let bus = FakeLogind::with_auto_session(AutoSession::Missing).serve_system_bus();
+        let context = bus.context();
+        let result = context.set_brightness(DeviceClass::Backlight, "panel", 1);
+        let error = context.check_logind("panel", result).unwrap_err();
+        assert!(!error.is_unsupported_write(), "{error:?}");
+        assert_eq!(
+            context.write_plan(&OnceLock::new()),
+            (WriteMethod::Logind, Some(WriteMethod::Sysfs))
+        );
+    }
+
+    #[test]
+    fn failing_logind_falls_back_without_being_abandoned() {
+        let sysfs = FakeSysfs::new();
+        sysfs.add_device("panel", 200, 50);
+        let logind = FakeLogind::with_auto_session(AutoSession::Failing);
+        let mut context = sysfs.context();
+        context.set_dbus_connection(logind.connect());
+        let device = context
+            .blocking_brightness_devices()
+            .next()
+            .unwrap()
+            .unwrap();
+        device.set(40).unwrap();
+        let written = fs::read_to_string(sysfs.device_dir("panel").join("brightness")).unwrap();
+        assert_eq!(written, "80");
+        assert_eq!(device.write_method().unwrap(), WriteMethod::Logind);
+    }
+
+    #[test]
+    fn both_errors_are_kept_when_falling_back_fails() {
+        let sysfs = FakeSysfs::new();
+        sysfs.add_device("panel", 200, 50);
+        // Writing fails for a directory, even as root.
+        fs::create_dir(sysfs.device_dir("panel").join("brightness")).unwrap();
+        let logind = FakeLogind::with_auto_session(AutoSession::Failing);
+        let mut context = sysfs.context();
+        context.set_dbus_connection(logind.connect());
+        let device = context
+            .blocking_brightness_devices()
+            .next()
+            .unwrap()
+            .unwrap();
+        let error = device.set(40).unwrap_err();
+        assert!(
+            matches!(error, Error::SettingBrightness { .. }),
+            "{error:?}"
+        );
+        let chain = describe(&error);
+        assert!(chain.contains("through logind"), "{chain}");
+        assert!(chain.contains("Device busy"), "{chain}");
+        assert!(chain.contains("Failed to write brightness"), "{chain}");
+    }
+
+    #[test]
+    fn sessions_are_looked_up_before_setting_brightness() {
+        let sysfs = FakeSysfs::new();
+        sysfs.add_device("panel", 200, 50);
+        let sessions = [
+            (LogindSession::Id("2".into()), SEAT_SESSION_PATH),
+            (LogindSession::Id("c3".into()), PID_SESSION_PATH),
+            (
+                LogindSession::ActiveOnSeat("seat0".into()),
+                SEAT_SESSION_PATH,
+            ),
+            (LogindSession::Pid(SESSION_PID), PID_SESSION_PATH),
+        ];
+        for (session, path) in sessions {
+            let logind = FakeLogind::default();
+            let mut context = sysfs.context();
+            context.set_dbus_connection(logind.connect());
+            context.set_logind_session(session.clone());
+            let device = context
+                .blocking_brightness_devices()
+                .next()
+                .unwrap()
+                .unwrap();
+            device.set(40).unwrap();
+            let expected = [("backlight".into(), "panel".into(), 80)];
+            assert_eq!(logind.calls_to(path), expected, "{session:?}");
+            assert_eq!(logind.calls(), [], "{session:?}");
+        }
+    }
+
+    #[test]
+    fn unknown_sessions_are_not_written() {
+        let logind = FakeLogind::default();
+        for session in [LogindSession::Id("7".into()), LogindSession::Pid(1)] {
+            let mut context = Context::with_sysfs_root(temp_path("sysfs"));
+            context.set_dbus_connection(logind.connect());
+            context.set_logind_session(session);
+            assert!(
+                context
+                    .set_brightness(DeviceClass::Backlight, "panel", 1)
+                    .is_err()
+            );
+        }
+        assert_eq!(logind.calls_to(SEAT_SESSION_PATH), []);
+        assert_eq!(logind.calls_to(PID_SESSION_PATH), []);
+    }
+
+    #[test]
+    fn unsupported_logind_is_remembered_per_session() {
+        let bus = FakeLogind::outdated().serve_system_bus();
+        let context = bus.context();
+        let result = context.set_brightness(DeviceClass::Backlight, "panel", 1);
+        assert!(context.check_logind("panel", result).is_err());
+        let remembered = OnceLock::new();
+        assert_eq!(context.write_plan(&remembered), (WriteMethod::Sysfs, None));
+
+        let mut seat_context = bus.context();
+        seat_context.set_logind_session(LogindSession::ActiveOnSeat("seat0".into()));
+        assert_eq!(
+            seat_context.write_plan(&remembered),
+            (WriteMethod::Logind, Some(WriteMethod::Sysfs))
+        );
+        let result = seat_context.set_brightness(DeviceClass::Backlight, "panel", 2);
+        assert!(seat_context.check_logind("panel", result).is_ok());
+    }
+
+    /// Returns a monitor on the given bus that never answers.
+    pub(crate) fn silent_monitor(bus: u32) -> DdcDevice {
+        let file = File::open("/dev/null").unwrap();
+        DdcDevice {
+            context: Context::new(),
+            bus,
+            connector: Connector {
+                path: PathBuf::new(),
+            },
+            ddc: Mutex::new(Ddc::new(I2cDevice { file })),
+            capabilities: OnceLock::new(),
+        }
+    }
+
+    #[test]
+    fn monitors_report_watching_as_unsupported() {
+        let inner =
+            blocking::DeviceImpl::Platform(BlockingDeviceImpl::Ddc(Box::new(silent_monitor(5))));
+        let device = blocking::BrightnessDevice::new(inner);
+        let mut changes = device.watch();
+        match changes.next() {
+            Some(Err(Error::GettingDeviceInfo { device, .. })) => assert_eq!(device, "i2c-5"),
+            other => panic!("expected an error, got {other:?}"),
+        }
+        assert!(changes.next().is_none());
+    }
+
+    #[test]
+    fn failed_ddc_probes_expire() {
+        let mut probes = DdcProbes(Vec::new());
+        let start = Instant::now();
+        assert_eq!(probes.get(3, b"monitor", start), None);
+        probes.insert(3, b"monitor".to_vec(), false, start);
+        probes.insert(4, b"other".to_vec(), true, start);
+        let later = start + DDC_PROBE_RETRY / 2;
+        assert_eq!(probes.get(3, b"monitor", later), Some(false));
+        assert_eq!(probes.get(3, b"monitor", start + DDC_PROBE_RETRY), None);
+        // Monitors supporting DDC/CI are not probed again.
+        let much_later = start + DDC_PROBE_RETRY * 10;
+        assert_eq!(probes.get(4, b"other", much_later), Some(true));
+    }
+
+    #[test]
+    fn ddc_probes_are_forgotten_when_the_monitor_changes() {
+        let mut probes = DdcProbes(Vec::new());
+        let now = Instant::now();
+        probes.insert(3, b"first".to_vec(), false, now);
+        assert_eq!(probes.get(3, b"second", now), None);
+        assert_eq!(probes.get(3, b"first", now), None);
+        probes.insert(3, b"second".to_vec(), true, now);
+        assert_eq!(probes.get(3, b"second", now), Some(true));
+    }
+
+    /// Returns the kind of the connector of a backlight device and whether it's internal.
+    fn connector_of(sysfs: &FakeSysfs, device: &str) -> Option<(String, bool)> {
+        Connector::of_backlight(&sysfs.context(), device).map(|c| (c.kind(), c.is_internal()))
+    }
+
+    #[test]
+    fn backlights_are_matched_with_their_connector() {
+        let sysfs = FakeSysfs::new();
+        let card = sysfs.add_card("0000:00:02.0", "card0");
+        let panel = sysfs.add_connector(&card, "card0-eDP-1", 3);
+        let monitor = sysfs.add_connector(&card, "card0-DP-1", 5);
+        // Registered under the connector.
+        sysfs.add_device("intel_backlight", 100, 50);
+        sysfs.set_parent("intel_backlight", &panel);
+        // Registered under the graphics card.
+        sysfs.add_device("amdgpu_bl0", 100, 50);
+        sysfs.set_parent("amdgpu_bl0", &card);
+        // Registered under the I2C bus of the connector.
+        sysfs.add_device("ddcci5", 100, 50);
+        sysfs.set_parent("ddcci5", &monitor.join("i2c-5"));
+
+        let internal = Some(("eDP-1".into(), true));
+        assert_eq!(connector_of(&sysfs, "intel_backlight"), internal);
+        assert_eq!(connector_of(&sysfs, "amdgpu_bl0"), internal);
+        assert_eq!(connector_of(&sysfs, "ddcci5"), Some(("DP-1".into(), false)));
+
+        let devices = sysfs
+            .context()
+            .blocking_brightness_devices()
+            .map(|d| {
+                let d = d.unwrap();
+                (
+                    d.device_name().unwrap(),
+                    d.connector_name(),
+                    d.is_internal(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert!(devices.contains(&("ddcci5".into(), Some("DP-1".into()), false)));
+        assert!(devices.contains(&("amdgpu_bl0".into(), Some("eDP-1".into()), true)));
+    }
+
+    #[test]
+    fn unmatched_backlights_are_internal_unless_ddcci() {
+        let sysfs = FakeSysfs::new();
+        let card = sysfs.add_card("0000:00:02.0", "card0");
+        sysfs.add_connector(&card, "card0-DP-1", 5);
+        sysfs.add_device("acpi_video0", 100, 50);
+        sysfs.add_device("ddcci7", 100, 50);
+
+        assert_eq!(connector_of(&sysfs, "acpi_video0"), None);
+        let context = sysfs.context();
+        let is_internal = |device: &str| {
+            SysfsDevice {
+                context: context.clone(),
+                class: DeviceClass::Backlight,
+                device: device.into(),
+                write_method: OnceLock::new(),
+            }
+            .is_internal()
+        };
+        assert!(is_internal("acpi_video0"));
+        assert!(!is_internal("ddcci7"));
+    }
+
+    #[test]
+    fn card_numbers_are_left_out_of_stable_paths() {
+        let strip = |path: &str| without_card_numbers(Path::new(path));
+        assert_eq!(
+            strip("/sys/devices/pci0000:00/0000:00:02.0/drm/card1/card1-eDP-1"),
+            "/sys/devices/pci0000:00/0000:00:02.0/drm/eDP-1"
+        );
+        assert_eq!(
+            strip("/sys/devices/pci0000:00/0000:00:01.0/0000:01:00.0/drm/card12/card12-DP-3"),
+            "/sys/devices/pci0000:00/0000:00:01.0/0000:01:00.0/drm/DP-3"
+        );
+        assert_eq!(
+            strip("/sys/devices/platform/dell-laptop/backlight/dell_backlight"),
+            "/sys/devices/platform/dell-laptop/backlight/dell_backlight"
+        );
+        assert_eq!(
+            strip("/sys/devices/cardreader/cards"),
+            "/sys/devices/cardreader/cards"
+        );
+    }
+
+    #[test]
+    fn sibling_backlights_have_different_ids() {
+        let sysfs = FakeSysfs::new();
+        let card = sysfs.add_card("0000:03:00.0", "card1");
+        sysfs.add_sibling_device(&card, "amdgpu_bl0");
+        sysfs.add_sibling_device(&card, "amdgpu_bl1");
+        let context = sysfs.context();
+        let ids = context
+            .blocking_brightness_devices()
+            .map(|d| d.unwrap().id())
+            .collect::<Vec<_>>();
+        assert_eq!(ids.len(), 2);
+        assert_ne!(ids[0], ids[1]);
+        for id in &ids {
+            assert!(!id.to_string().contains("card1"), "{id}");
+            let device = blocking::find_by_id(context.blocking_brightness_devices(), id)
+                .unwrap()
+                .unwrap();
+            assert_eq!(device.id(), *id);
+        }
+    }
+
+    /// Returns the names of the devices found in a context.
+    fn device_names(context: &Context) -> Vec<String> {
+        context
+            .blocking_brightness_devices()
+            .map(|d| d.unwrap().device_name().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn leds_are_found_when_selected() {
+        let sysfs = FakeSysfs::new();
+        sysfs.add_device("panel", 200, 50);
+        sysfs.add_led("phy0-led", 255, 255);
+        sysfs.add_led("input3::capslock", 1, 0);
+        sysfs.add_led("tpacpi::kbd_backlight", 2, 1);
+        let mut context = sysfs.context();
+        assert_eq!(device_names(&context), ["panel"]);
+
+        context.set_device_classes([DeviceClass::Led, DeviceClass::KeyboardLed]);
+        assert_eq!(
+            device_names(&context),
+            ["tpacpi::kbd_backlight", "phy0-led"]
+        );
+
+        context.set_device_classes([DeviceClass::KeyboardLed, DeviceClass::Backlight]);
+        assert_eq!(device_names(&context), ["panel", "tpacpi::kbd_backlight"]);
+        let keyboard = context
+            .blocking_brightness_devices()
+            .nth(1)
+            .unwrap()
+            .unwrap();
+        assert_eq!(keyboard.get().unwrap(), 50);
+        keyboard.set(100).unwrap();
+        let dir = sysfs.0.join(LEDS_CLASS).join("tpacpi::kbd_backlight");
+        assert_eq!(fs::read_to_string(dir.join("brightness")).unwrap(), "2");
+    }
+
+    /// Adds a powered up backlight whose brightness was set to `actual`.
+    fn add_powered_device(sysfs: &FakeSysfs, name: &str, max: u32, actual: u32) {
+        sysfs.add_device(name, max, actual);
+        let dir = sysfs.device_dir(name);
+        fs::write(dir.join("brightness"), actual.to_string()).unwrap();
+        fs::write(dir.join("bl_power"), BL_POWER_ON.to_string()).unwrap();
+    }
+
+    fn first_device(context: &Context) -> blocking::BrightnessDevice {
+        context
+            .blocking_brightness_devices()
+            .next()
+            .unwrap()
+            .unwrap()
+    }
+
+    #[test]
+    fn brightness_is_restored_on_power_up() {
+        let sysfs = FakeSysfs::new();
+        add_powered_device(&sysfs, "panel", 200, 80);
+        let dir = sysfs.device_dir("panel");
+        let device = first_device(&sysfs.context());
+        device.set_power(PowerState::Off).unwrap();
+        assert_eq!(fs::read_to_string(dir.join("bl_power")).unwrap(), "4");
+        assert_eq!(device.power_state().unwrap(), PowerState::Off);
+
+        // The driver resets the actual brightness, and the device is found again.
+        fs::write(dir.join("actual_brightness"), "0").unwrap();
+        let device = first_device(&sysfs.context());
+        device.set_power(PowerState::On).unwrap();
+        assert_eq!(fs::read_to_string(dir.join("bl_power")).unwrap(), "0");
+        assert_eq!(device.power_state().unwrap(), PowerState::On);
+        assert_eq!(fs::read_to_string(dir.join("brightness")).unwrap(), "80");
+    }
+
+    #[test]
+    fn brightness_is_restored_through_logind() {
+        let sysfs = FakeSysfs::new();
+        add_powered_device(&sysfs, "panel", 200, 80);
+        let logind = FakeLogind::default();
+        let mut context = sysfs.context();
+        context.set_dbus_connection(logind.connect());
+        let device = first_device(&context);
+        device.set_power(PowerState::Off).unwrap();
+        device.set_power(PowerState::On).unwrap();
+        // Brightness that the driver kept is not written again.
+        assert_eq!(logind.calls(), []);
+
+        device.set_power(PowerState::Off).unwrap();
+        fs::write(sysfs.device_dir("panel").join("actual_brightness"), "0").unwrap();
+        device.set_power(PowerState::On).unwrap();
+        assert_eq!(logind.calls(), [("backlight".into(), "panel".into(), 80)]);
+    }
+
+    #[test]
+    fn only_backlights_have_a_power_state() {
+        let sysfs = FakeSysfs::new();
+        sysfs.add_led("tpacpi::kbd_backlight", 2, 1);
+        let mut context = sysfs.context();
+        context.set_device_classes([DeviceClass::KeyboardLed]);
+        let device = first_device(&context);
+        assert!(matches!(
+            device.power_state(),
+            Err(Error::GettingDeviceInfo { .. })
+        ));
+        assert!(matches!(
+            device.set_power(PowerState::Off),
+            Err(Error::GettingDeviceInfo { .. })
+        ));
+    }
+}
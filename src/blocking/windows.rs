@@ -2,7 +2,13 @@
 
 //! Platform-specific implementation for Windows.
 
-use crate::{Error, blocking::BrightnessDevice};
+use crate::{
+    DeviceType, Error, Scale, VcpValue,
+    backlight::Parent,
+    blocking::{BrightnessDevice, DeviceImpl},
+    conversion::{percentage_to_raw, raw_to_percentage},
+    watch::Watcher,
+};
 use itertools::Either;
 use std::{
     collections::HashMap,
@@ -15,18 +21,26 @@ use std::{
 use windows::{
     Win32::{
         Devices::Display::{
-            DISPLAY_BRIGHTNESS, DISPLAYCONFIG_DEVICE_INFO_GET_TARGET_NAME, DISPLAYCONFIG_MODE_INFO,
-            DISPLAYCONFIG_MODE_INFO_TYPE_TARGET, DISPLAYCONFIG_OUTPUT_TECHNOLOGY_INTERNAL,
-            DISPLAYCONFIG_PATH_INFO, DISPLAYCONFIG_TARGET_DEVICE_NAME,
-            DISPLAYCONFIG_VIDEO_OUTPUT_TECHNOLOGY, DISPLAYPOLICY_AC, DISPLAYPOLICY_DC,
-            DestroyPhysicalMonitor, DisplayConfigGetDeviceInfo, GetDisplayConfigBufferSizes,
-            GetMonitorBrightness, GetNumberOfPhysicalMonitorsFromHMONITOR,
-            GetPhysicalMonitorsFromHMONITOR, IOCTL_VIDEO_QUERY_DISPLAY_BRIGHTNESS,
+            CapabilitiesRequestAndCapabilitiesReply, DISPLAY_BRIGHTNESS,
+            DISPLAYCONFIG_DEVICE_INFO_GET_TARGET_NAME, DISPLAYCONFIG_MODE_INFO,
+            DISPLAYCONFIG_MODE_INFO_TYPE_TARGET,
+            DISPLAYCONFIG_OUTPUT_TECHNOLOGY_DISPLAYPORT_EMBEDDED,
+            DISPLAYCONFIG_OUTPUT_TECHNOLOGY_DISPLAYPORT_EXTERNAL,
+            DISPLAYCONFIG_OUTPUT_TECHNOLOGY_DVI, DISPLAYCONFIG_OUTPUT_TECHNOLOGY_HD15,
+            DISPLAYCONFIG_OUTPUT_TECHNOLOGY_HDMI, DISPLAYCONFIG_OUTPUT_TECHNOLOGY_INTERNAL,
+            DISPLAYCONFIG_OUTPUT_TECHNOLOGY_LVDS, DISPLAYCONFIG_PATH_INFO,
+            DISPLAYCONFIG_TARGET_DEVICE_NAME, DISPLAYCONFIG_VIDEO_OUTPUT_TECHNOLOGY,
+            DISPLAYPOLICY_AC, DISPLAYPOLICY_DC, DestroyPhysicalMonitor, DisplayConfigGetDeviceInfo,
+            GetCapabilitiesStringLength, GetDisplayConfigBufferSizes, GetMonitorBrightness,
+            GetNumberOfPhysicalMonitorsFromHMONITOR, GetPhysicalMonitorsFromHMONITOR,
+            GetVCPFeatureAndVCPFeatureReply, IOCTL_VIDEO_QUERY_DISPLAY_BRIGHTNESS,
             IOCTL_VIDEO_QUERY_SUPPORTED_BRIGHTNESS, IOCTL_VIDEO_SET_DISPLAY_BRIGHTNESS,
             PHYSICAL_MONITOR, QDC_ONLY_ACTIVE_PATHS, QueryDisplayConfig, SetMonitorBrightness,
+            SetVCPFeature,
         },
         Foundation::{
-            CloseHandle, ERROR_ACCESS_DENIED, ERROR_SUCCESS, HANDLE, LPARAM, RECT, WIN32_ERROR,
+            CloseHandle, ERROR_ACCESS_DENIED, ERROR_FILE_NOT_FOUND, ERROR_SUCCESS, HANDLE, LPARAM,
+            RECT, WIN32_ERROR,
         },
         Graphics::Gdi::{
             DISPLAY_DEVICE_ACTIVE, DISPLAY_DEVICEW, EnumDisplayDevicesW, EnumDisplayMonitors,
@@ -36,10 +50,13 @@ use windows::{
             CreateFileW, FILE_GENERIC_READ, FILE_GENERIC_WRITE, FILE_SHARE_READ, FILE_SHARE_WRITE,
             OPEN_EXISTING,
         },
-        System::IO::DeviceIoControl,
+        System::{
+            IO::DeviceIoControl,
+            Registry::{HKEY_LOCAL_MACHINE, RRF_RT_REG_BINARY, RegGetValueW},
+        },
         UI::WindowsAndMessaging::EDD_GET_DEVICE_INTERFACE_NAME,
     },
-    core::{BOOL, Error as WinError, PCWSTR},
+    core::{BOOL, Error as WinError, PCWSTR, w},
 };
 
 /// Windows-specific brightness functionality.
@@ -70,9 +87,79 @@ pub(crate) struct BlockingDeviceImpl {
 }
 
 impl BlockingDeviceImpl {
-    fn is_internal(&self) -> bool {
+    pub(crate) fn is_internal(&self) -> bool {
         self.output_technology == DISPLAYCONFIG_OUTPUT_TECHNOLOGY_INTERNAL
     }
+
+    /// Returns the connector type, as Windows doesn't number connectors.
+    pub(crate) fn connector_name(&self) -> Option<String> {
+        let name = match self.output_technology {
+            DISPLAYCONFIG_OUTPUT_TECHNOLOGY_HD15 => "VGA",
+            DISPLAYCONFIG_OUTPUT_TECHNOLOGY_DVI => "DVI",
+            DISPLAYCONFIG_OUTPUT_TECHNOLOGY_HDMI => "HDMI",
+            DISPLAYCONFIG_OUTPUT_TECHNOLOGY_DISPLAYPORT_EXTERNAL => "DP",
+            DISPLAYCONFIG_OUTPUT_TECHNOLOGY_DISPLAYPORT_EMBEDDED => "eDP",
+            DISPLAYCONFIG_OUTPUT_TECHNOLOGY_LVDS => "LVDS",
+            _ => return None,
+        };
+        Some(name.into())
+    }
+
+    pub(crate) fn scale(&self) -> Scale {
+        Scale::Unknown
+    }
+
+    pub(crate) fn device_type(&self) -> DeviceType {
+        DeviceType::Unknown
+    }
+
+    pub(crate) fn parent(&self) -> Option<Parent> {
+        None
+    }
+
+    pub(crate) fn watcher(&self) -> Result<Watcher, SysError> {
+        Ok(Watcher::Interval)
+    }
+
+    pub(crate) fn get_vcp(&self, code: u8) -> Result<VcpValue, SysError> {
+        if self.is_internal() {
+            return Err(self.ddc_unsupported());
+        }
+        ddcci_get_vcp_feature(self, code)
+    }
+
+    pub(crate) fn set_vcp(&self, code: u8, value: u16) -> Result<(), SysError> {
+        if self.is_internal() {
+            return Err(self.ddc_unsupported());
+        }
+        ddcci_set_vcp_feature(self, code, value)
+    }
+
+    pub(crate) fn capabilities_string(&self) -> Result<String, SysError> {
+        if self.is_internal() {
+            return Err(self.ddc_unsupported());
+        }
+        ddcci_capabilities_string(self)
+    }
+
+    pub(crate) fn edid(&self) -> Result<Option<Vec<u8>>, SysError> {
+        registry_edid(self)
+    }
+
+    pub(crate) fn driver(&self) -> String {
+        "display".into()
+    }
+
+    /// Returns the device path, which identifies the monitor and the port it is connected to.
+    pub(crate) fn stable_path(&self) -> String {
+        self.device_path.clone()
+    }
+
+    fn ddc_unsupported(&self) -> SysError {
+        SysError::DdcUnsupported {
+            device_name: self.device_name.clone(),
+        }
+    }
 }
 
 /// A safe wrapper for a physical monitor handle that implements `Drop` to call `DestroyPhysicalMonitor`
@@ -147,6 +234,36 @@ impl crate::blocking::Brightness for BlockingDeviceImpl {
         }
         Ok(())
     }
+
+    fn get_raw(&self) -> Result<u32, Error> {
+        Ok(if self.is_internal() {
+            ioctl_query_display_brightness(self)?
+        } else {
+            ddcci_get_monitor_brightness(self)?.raw()
+        })
+    }
+
+    fn max_raw(&self) -> Result<u32, Error> {
+        Ok(if self.is_internal() {
+            // IOCTL brightness levels are values from 0 to 100.
+            100
+        } else {
+            ddcci_get_monitor_brightness(self)?.max_raw()
+        })
+    }
+
+    fn set_raw(&self, value: u32) -> Result<(), Error> {
+        if self.is_internal() {
+            let supported = ioctl_query_supported_brightness(self)?;
+            let new_value = supported.get_nearest(value);
+            ioctl_set_display_brightness(self, new_value)?;
+        } else {
+            let current = ddcci_get_monitor_brightness(self)?;
+            let new_value = current.raw_to_current(value);
+            ddcci_set_monitor_brightness(self, new_value)?;
+        }
+        Ok(())
+    }
 }
 
 pub(crate) fn brightness_devices() -> impl Iterator<Item = Result<BlockingDeviceImpl, SysError>> {
@@ -429,6 +546,30 @@ pub(crate) enum SysError {
         device_name: String,
         source: WinError,
     },
+    #[error("Failed to get VCP feature {code:#04x} (DDCCI)")]
+    GettingVcpFeature {
+        device_name: String,
+        code: u8,
+        source: WinError,
+    },
+    #[error("Failed to set VCP feature {code:#04x} (DDCCI)")]
+    SettingVcpFeature {
+        device_name: String,
+        code: u8,
+        source: WinError,
+    },
+    #[error("Failed to get monitor capabilities (DDCCI)")]
+    GettingCapabilities {
+        device_name: String,
+        source: WinError,
+    },
+    #[error("Failed to read monitor EDID from the registry")]
+    ReadingEdid {
+        device_name: String,
+        source: WinError,
+    },
+    #[error("Internal displays are not controlled through DDC/CI")]
+    DdcUnsupported { device_name: String },
 }
 
 impl From<SysError> for Error {
@@ -448,7 +589,11 @@ impl From<SysError> for Error {
             SysError::IoctlQuerySupportedBrightness { device_name, .. }
             | SysError::IoctlQueryDisplayBrightness { device_name, .. }
             | SysError::IoctlQueryDisplayBrightnessUnexpectedResponse { device_name }
-            | SysError::GettingMonitorBrightness { device_name, .. } => Error::GettingDeviceInfo {
+            | SysError::GettingMonitorBrightness { device_name, .. }
+            | SysError::GettingVcpFeature { device_name, .. }
+            | SysError::GettingCapabilities { device_name, .. }
+            | SysError::ReadingEdid { device_name, .. }
+            | SysError::DdcUnsupported { device_name } => Error::GettingDeviceInfo {
                 device: device_name.clone(),
                 source: Box::new(e),
             },
@@ -457,6 +602,13 @@ impl From<SysError> for Error {
                 device: device_name.clone(),
                 source: Box::new(e),
             },
+            SysError::SettingVcpFeature {
+                device_name, code, ..
+            } => Error::SettingVcpFeature {
+                device: device_name.clone(),
+                code: *code,
+                source: Box::new(e),
+            },
         }
     }
 }
@@ -478,6 +630,8 @@ where
     status.ok().map_err(f)
 }
 
+/// Brightness of a monitor as reported by DDC/CI. Raw values are relative to `min`, so that they
+/// agree with percentages.
 #[derive(Debug, Default)]
 struct DdcciBrightnessValues {
     min: u32,
@@ -486,17 +640,24 @@ struct DdcciBrightnessValues {
 }
 
 impl DdcciBrightnessValues {
+    fn raw(&self) -> u32 {
+        self.current.clamp(self.min, self.max.max(self.min)) - self.min
+    }
+
+    fn max_raw(&self) -> u32 {
+        self.max.saturating_sub(self.min)
+    }
+
+    fn raw_to_current(&self, raw: u32) -> u32 {
+        raw.min(self.max_raw()) + self.min
+    }
+
     fn get_current_percentage(&self) -> u32 {
-        let normalised_max = (self.max - self.min) as f64;
-        let normalised_current = (self.current - self.min) as f64;
-        (normalised_current / normalised_max * 100.0).round() as u32
+        raw_to_percentage(self.raw(), self.max_raw())
     }
 
     fn percentage_to_current(&self, percentage: u32) -> u32 {
-        let normalised_max = (self.max - self.min) as f64;
-        let fraction = percentage as f64 / 100.0;
-        let normalised_current = fraction * normalised_max;
-        normalised_current.round() as u32 + self.min
+        self.raw_to_current(percentage_to_raw(percentage, self.max_raw()))
     }
 }
 
@@ -531,6 +692,127 @@ fn ddcci_set_monitor_brightness(device: &BlockingDeviceImpl, value: u32) -> Resu
     }
 }
 
+fn ddcci_get_vcp_feature(device: &BlockingDeviceImpl, code: u8) -> Result<VcpValue, SysError> {
+    unsafe {
+        let mut current = 0;
+        let mut max = 0_u32;
+        BOOL(GetVCPFeatureAndVCPFeatureReply(
+            device.physical_monitor.0,
+            code,
+            None,
+            &mut current,
+            Some(&mut max as *mut u32),
+        ))
+        .ok()
+        .map(|_| VcpValue {
+            // VCP values are 16-bit; the upper bits are always zero.
+            current: current as u16,
+            max: max as u16,
+        })
+        .map_err(|e| SysError::GettingVcpFeature {
+            device_name: device.device_name.clone(),
+            code,
+            source: e,
+        })
+    }
+}
+
+fn ddcci_set_vcp_feature(
+    device: &BlockingDeviceImpl,
+    code: u8,
+    value: u16,
+) -> Result<(), SysError> {
+    unsafe {
+        BOOL(SetVCPFeature(device.physical_monitor.0, code, value.into()))
+            .ok()
+            .map_err(|e| SysError::SettingVcpFeature {
+                device_name: device.device_name.clone(),
+                code,
+                source: e,
+            })
+    }
+}
+
+fn ddcci_capabilities_string(device: &BlockingDeviceImpl) -> Result<String, SysError> {
+    let error = |e| SysError::GettingCapabilities {
+        device_name: device.device_name.clone(),
+        source: e,
+    };
+    unsafe {
+        let mut length = 0;
+        BOOL(GetCapabilitiesStringLength(
+            device.physical_monitor.0,
+            &mut length,
+        ))
+        .ok()
+        .map_err(error)?;
+        let mut buffer = vec![0; length as usize];
+        BOOL(CapabilitiesRequestAndCapabilitiesReply(
+            device.physical_monitor.0,
+            &mut buffer,
+        ))
+        .ok()
+        .map_err(error)?;
+        // The length includes the terminating null character.
+        let end = buffer.iter().position(|&b| b == 0).unwrap_or(buffer.len());
+        Ok(String::from_utf8_lossy(&buffer[..end]).into_owned())
+    }
+}
+
+/// Reads the EDID of a monitor from the registry key of its device instance.
+///
+/// The device path has the form `\\?\DISPLAY#<hardware ID>#<instance>#{<interface class>}`.
+fn registry_edid(device: &BlockingDeviceImpl) -> Result<Option<Vec<u8>>, SysError> {
+    let mut parts = device.device_path.trim_start_matches(r"\\?\").split('#');
+    let (Some(enumerator), Some(hardware_id), Some(instance)) =
+        (parts.next(), parts.next(), parts.next())
+    else {
+        return Ok(None);
+    };
+    let key = format!(
+        r"SYSTEM\CurrentControlSet\Enum\{enumerator}\{hardware_id}\{instance}\Device Parameters"
+    );
+    let key = key.encode_utf16().chain(once(0)).collect::<Vec<_>>();
+    let error = |e: WIN32_ERROR| SysError::ReadingEdid {
+        device_name: device.device_name.clone(),
+        source: e.into(),
+    };
+    let mut size = 0_u32;
+    let status = unsafe {
+        RegGetValueW(
+            HKEY_LOCAL_MACHINE,
+            PCWSTR(key.as_ptr()),
+            w!("EDID"),
+            RRF_RT_REG_BINARY,
+            None,
+            None,
+            Some(&mut size as *mut u32),
+        )
+    };
+    match status {
+        ERROR_SUCCESS => {}
+        ERROR_FILE_NOT_FOUND => return Ok(None),
+        _ => return Err(error(status)),
+    }
+    let mut edid = vec![0_u8; size as usize];
+    let status = unsafe {
+        RegGetValueW(
+            HKEY_LOCAL_MACHINE,
+            PCWSTR(key.as_ptr()),
+            w!("EDID"),
+            RRF_RT_REG_BINARY,
+            None,
+            Some(edid.as_mut_ptr() as *mut c_void),
+            Some(&mut size as *mut u32),
+        )
+    };
+    if status != ERROR_SUCCESS {
+        return Err(error(status));
+    }
+    edid.truncate(size as usize);
+    Ok(Some(edid))
+}
+
 /// Each level is a value from 0 to 100
 #[derive(Debug)]
 struct IoctlSupportedBrightnessLevels(Vec<u8>);
@@ -641,14 +923,80 @@ fn ioctl_set_display_brightness(device: &BlockingDeviceImpl, value: u8) -> Resul
 
 impl BrightnessExt for BrightnessDevice {
     fn device_description(&self) -> Result<String, Error> {
-        Ok(self.0.device_description.clone())
+        match &self.inner {
+            DeviceImpl::Platform(d) => Ok(d.device_description.clone()),
+            #[cfg(feature = "mock")]
+            DeviceImpl::Mock(d) => Err(d.unsupported()),
+        }
     }
 
     fn device_registry_key(&self) -> Result<String, Error> {
-        Ok(self.0.device_key.clone())
+        match &self.inner {
+            DeviceImpl::Platform(d) => Ok(d.device_key.clone()),
+            #[cfg(feature = "mock")]
+            DeviceImpl::Mock(d) => Err(d.unsupported()),
+        }
     }
 
     fn device_path(&self) -> Result<String, Error> {
-        Ok(self.0.device_path.clone())
+        match &self.inner {
+            DeviceImpl::Platform(d) => Ok(d.device_path.clone()),
+            #[cfg(feature = "mock")]
+            DeviceImpl::Mock(d) => Err(d.unsupported()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ddcci(min: u32, current: u32, max: u32) -> DdcciBrightnessValues {
+        DdcciBrightnessValues { min, current, max }
+    }
+
+    #[test]
+    fn ddcci_raw_values_are_relative_to_the_minimum() {
+        let values = ddcci(20, 70, 120);
+        assert_eq!(values.raw(), 50);
+        assert_eq!(values.max_raw(), 100);
+        assert_eq!(values.get_current_percentage(), 50);
+        assert_eq!(values.raw_to_current(0), 20);
+        assert_eq!(values.raw_to_current(100), 120);
+        assert_eq!(values.raw_to_current(500), 120);
+        assert_eq!(values.percentage_to_current(50), 70);
+    }
+
+    #[test]
+    fn ddcci_raw_values_agree_with_percentages() {
+        for (min, max) in [(0, 100), (0, 255), (10, 50), (3, 7)] {
+            for current in min..=max {
+                let values = ddcci(min, current, max);
+                let percentage = raw_to_percentage(values.raw(), values.max_raw());
+                assert_eq!(values.get_current_percentage(), percentage);
+                assert_eq!(values.raw_to_current(values.raw()), current);
+            }
+        }
+    }
+
+    #[test]
+    fn ddcci_values_out_of_range_are_tolerated() {
+        assert_eq!(ddcci(20, 10, 120).raw(), 0);
+        assert_eq!(ddcci(20, 130, 120).raw(), 100);
+        let inverted = ddcci(50, 40, 30);
+        assert_eq!((inverted.raw(), inverted.max_raw()), (0, 0));
+        assert_eq!(inverted.get_current_percentage(), 0);
+    }
+
+    #[test]
+    fn nearest_supported_ioctl_level_is_chosen() {
+        let levels = IoctlSupportedBrightnessLevels(vec![0, 25, 50, 100]);
+        assert_eq!(levels.get_nearest(30), 25);
+        assert_eq!(levels.get_nearest(80), 100);
+        assert_eq!(levels.get_nearest(100), 100);
+        assert_eq!(
+            IoctlSupportedBrightnessLevels(Vec::new()).get_nearest(40),
+            0
+        );
     }
 }
@@ -0,0 +1,337 @@
+// Copyright (C) 2022 The brightness project authors. Distributed under the 0BSD license.
+
+//! Parsing of MCCS capabilities strings reported by monitors through DDC/CI.
+
+use crate::Error;
+use std::{collections::BTreeMap, fmt, str::FromStr};
+
+/// Capabilities of a monitor, as reported in its MCCS capabilities string.
+///
+/// Monitors commonly deviate from the specification, so parsing is lenient: the enclosing
+/// parentheses may be missing, hexadecimal values need not be separated by spaces, unknown
+/// entries are ignored and a malformed MCCS version is left out.
+///
+/// # Example
+///
+/// ```rust
+/// use brightness::Capabilities;
+///
+/// let caps: Capabilities = "(prot(monitor)type(LCD)model(U2415)cmds(01 02 03 07 0C E3 F3)\
+///     vcp(02 04 05 08 10 12 14(05 08 0B 0C) 16 18 1A 52 60(01 0F 11) AA(01 02) AC AE B2 B6 C6 \
+///     C8 C9 D6(01 04 05) DC(00 02 03 05) DF E0 E1 E2(00 01 02 04 0E 12 14 19) F0(00 08) \
+///     F1(01 02) F2 FD)mswhql(1)asset_eep(40)mccs_ver(2.1))"
+///     .parse()
+///     .unwrap();
+/// assert_eq!(caps.model.as_deref(), Some("U2415"));
+/// assert!(caps.supports(0x10));
+/// assert_eq!(caps.allowed_values(0x60), Some(&[0x01, 0x0f, 0x11][..]));
+/// assert_eq!(caps.mccs_version.unwrap().to_string(), "2.1");
+///
+/// let caps: Capabilities = "prot(monitor)type(lcd)model(LG FULL HD)cmds(01 02 03 0C E3 F3)\
+///     vcp(020406080B0C0E10121416181A52606C6E7087ACAEB6C0C6C8C9D6(01 04)DFE4E5E6E7E8E9EAEBED)\
+///     mccs_ver(2.1)mswhql(1)"
+///     .parse()
+///     .unwrap();
+/// assert_eq!(caps.display_type.as_deref(), Some("lcd"));
+/// assert_eq!(caps.allowed_values(0xd6), Some(&[0x01, 0x04][..]));
+/// assert_eq!(caps.allowed_values(0xed), Some(&[][..]));
+///
+/// assert!("(prot(monitor)vcp(10 12".parse::<Capabilities>().is_err());
+/// ```
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Capabilities {
+    /// Protocol class, normally `monitor`.
+    pub protocol: Option<String>,
+    /// Display technology, e.g. `LCD`.
+    pub display_type: Option<String>,
+    /// Model name.
+    pub model: Option<String>,
+    /// Supported DDC/CI commands.
+    pub commands: Vec<u8>,
+    /// Supported VCP features by code, with the values allowed for features with a set of
+    /// possible values. Continuous features have no listed values.
+    pub vcp_features: BTreeMap<u8, Vec<u8>>,
+    /// Implemented MCCS version.
+    pub mccs_version: Option<MccsVersion>,
+}
+
+impl Capabilities {
+    /// Returns whether a VCP feature is supported.
+    pub fn supports(&self, code: u8) -> bool {
+        self.vcp_features.contains_key(&code)
+    }
+
+    /// Returns the values allowed for a supported VCP feature. Continuous features have no listed
+    /// values.
+    pub fn allowed_values(&self, code: u8) -> Option<&[u8]> {
+        self.vcp_features.get(&code).map(Vec::as_slice)
+    }
+}
+
+impl FromStr for Capabilities {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        let s = s.trim_matches(|c: char| c.is_whitespace() || c == '\0');
+        let mut rest = match s.strip_prefix('(') {
+            // Text after the enclosing parentheses, e.g. an extra closing parenthesis, is ignored.
+            Some(inner) => closing(inner).map_or(inner, |end| &inner[..end]),
+            None => s,
+        };
+        let mut caps = Capabilities::default();
+        while let Some(open) = rest.find('(') {
+            let tag = rest[..open].trim_matches(|c: char| !c.is_ascii_alphanumeric() && c != '_');
+            let inner = &rest[open + 1..];
+            let end = closing(inner).ok_or_else(|| invalid(format!("unclosed {tag} entry")))?;
+            let value = inner[..end].trim();
+            match tag {
+                "prot" => caps.protocol = Some(value.into()),
+                "type" => caps.display_type = Some(value.into()),
+                "model" => caps.model = Some(value.into()),
+                "cmds" => caps.commands = parse_bytes(value)?,
+                "vcp" => caps.vcp_features = parse_vcp(value)?,
+                "mccs_ver" => caps.mccs_version = value.parse().ok(),
+                _ => {}
+            }
+            rest = &inner[end + 1..];
+        }
+        Ok(caps)
+    }
+}
+
+/// MCCS version, e.g. 2.2.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct MccsVersion {
+    /// Major version.
+    pub major: u8,
+    /// Minor version.
+    pub minor: u8,
+}
+
+impl fmt::Display for MccsVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}", self.major, self.minor)
+    }
+}
+
+impl FromStr for MccsVersion {
+    type Err = Error;
+
+    /// Parses a version like `2.2`. Suffixes some monitors append to the minor version, like in
+    /// `2.2a`, are ignored.
+    fn from_str(s: &str) -> Result<Self, Error> {
+        let number = |s: &str| {
+            let end = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+            s[..end].parse().ok()
+        };
+        s.trim()
+            .split_once('.')
+            .and_then(|(major, minor)| {
+                Some(MccsVersion {
+                    major: number(major)?,
+                    minor: number(minor)?,
+                })
+            })
+            .ok_or_else(|| invalid(format!("invalid MCCS version {s:?}")))
+    }
+}
+
+/// Returns the index of the parenthesis closing a group whose opening parenthesis precedes `s`.
+fn closing(s: &str) -> Option<usize> {
+    let mut depth = 0_usize;
+    for (i, c) in s.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' if depth == 0 => return Some(i),
+            ')' => depth -= 1,
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Parses VCP codes, each optionally followed by its allowed values in parentheses.
+fn parse_vcp(s: &str) -> Result<BTreeMap<u8, Vec<u8>>, Error> {
+    let mut features = BTreeMap::new();
+    let mut last = None;
+    let mut rest = s.trim_start();
+    while !rest.is_empty() {
+        if let Some(inner) = rest.strip_prefix('(') {
+            let end = closing(inner).ok_or_else(|| invalid("unclosed VCP values".into()))?;
+            let code = last.ok_or_else(|| invalid("VCP values without code".into()))?;
+            features.insert(code, parse_bytes(&inner[..end])?);
+            rest = &inner[end + 1..];
+        } else {
+            let (code, r) = hex_byte(rest)?;
+            features.insert(code, Vec::new());
+            last = Some(code);
+            rest = r;
+        }
+        rest = rest.trim_start();
+    }
+    Ok(features)
+}
+
+/// Parses hexadecimal bytes, separated by spaces or not. Nested groups of values, which some MCCS
+/// 3 monitors report, are flattened.
+fn parse_bytes(s: &str) -> Result<Vec<u8>, Error> {
+    let mut bytes = Vec::new();
+    let mut rest = s.trim_start_matches(|c: char| c.is_whitespace() || c == '(' || c == ')');
+    while !rest.is_empty() {
+        let (byte, r) = hex_byte(rest)?;
+        bytes.push(byte);
+        rest = r.trim_start_matches(|c: char| c.is_whitespace() || c == '(' || c == ')');
+    }
+    Ok(bytes)
+}
+
+/// Parses a byte written as two hexadecimal digits at the start of `s`. Returns the byte and the
+/// rest of `s`.
+fn hex_byte(s: &str) -> Result<(u8, &str), Error> {
+    match s.as_bytes() {
+        [hi, lo, ..] if hi.is_ascii_hexdigit() && lo.is_ascii_hexdigit() => {
+            let byte = u8::from_str_radix(&s[..2], 16).expect("two hexadecimal digits");
+            Ok((byte, &s[2..]))
+        }
+        _ => {
+            let context = s.chars().take(8).collect::<String>();
+            Err(invalid(format!("invalid hexadecimal byte at {context:?}")))
+        }
+    }
+}
+
+fn invalid(reason: String) -> Error {
+    Error::InvalidCapabilities(reason)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Capabilities strings as reported by monitors, including their quirks.
+    const CORPUS: [&str; 6] = [
+        "(prot(monitor)type(LCD)model(U2415)cmds(01 02 03 07 0C E3 F3)vcp(02 04 05 08 10 12 \
+         14(05 08 0B 0C) 16 18 1A 52 60(01 0F 11) AA(01 02) AC AE B2 B6 C6 C8 C9 D6(01 04 05) \
+         DC(00 02 03 05) DF E0 E1 E2(00 01 02 04 0E 12 14 19) F0(00 08) F1(01 02) F2 FD)\
+         mswhql(1)asset_eep(40)mccs_ver(2.1))",
+        // No enclosing parentheses, unspaced values.
+        "prot(monitor)type(lcd)model(LG FULL HD)cmds(01 02 03 0C E3 F3)vcp(020406080B0C0E10121416\
+         181A52606C6E7087ACAEB6C0C6C8C9D6(01 04)DFE4E5E6E7E8E9EAEBED)mccs_ver(2.1)mswhql(1)",
+        // Terminated by a null byte.
+        "(prot(monitor)type(LCD)model(HP Z27n)cmds(01 02 03 07 0C E3 F3)vcp(02 04 05 08 10 12 \
+         14(01 05 06 08 0B) 16 18 1A 52 60(0F 11 12) 62 6C 6E 70 86(02 0B) 87 AC AE B6 C0 C6 C8 \
+         C9 CA(01 02) CC(02 03 04 05 06 09 0A 0D 12 14 1E) D6(01 04 05) DC(00 02 03 05) DF)\
+         mswhql(1)asset_eep(40)mccs_ver(2.2))\0",
+        // An extra closing parenthesis.
+        "(prot(monitor)type(lcd)model(S24D300)cmds(01 02 03 07 0C E3 F3)vcp(02 04 05 08 10 12 \
+         14(05 08 0B) 16 18 1A 52 60(0F 10 11) AC AE B2 B6 C6 C8 C9 D6(01 04 05) DC(00 01 02 03 \
+         04 05) DF FD)mccs_ver(2.0)mswhql(1)))",
+        // MCCS 3 nested groups of values.
+        "(prot(display)type(lcd)model(P2715Q)cmds(01 02 03 07 0C E3 F3)vcp(02 04 05 08 10 12 \
+         14((05 06)(08 0B)) 16 18 1A 60((0F)(11 12)) AC AE B6 C6 C8 DF)mccs_ver(3.0))",
+        // Unspaced commands and a suffixed MCCS version.
+        "(prot(monitor)type(LCD)model(VG27A)cmds(010203070CE3F3)vcp(0204050810121416181A6062\
+         (00 0F)DF)mccs_ver(2.2a))",
+    ];
+
+    fn parse(s: &str) -> Capabilities {
+        s.parse().unwrap_or_else(|e| panic!("{s:?}: {e:?}"))
+    }
+
+    #[test]
+    fn corpus_is_parsed() {
+        let caps = CORPUS.map(parse);
+        let models = caps.iter().map(|c| c.model.as_deref().unwrap());
+        assert!(models.eq([
+            "U2415",
+            "LG FULL HD",
+            "HP Z27n",
+            "S24D300",
+            "P2715Q",
+            "VG27A"
+        ]));
+        for c in &caps {
+            assert!(c.supports(0x10) || c.supports(0x0b), "{c:?}");
+            assert_eq!(c.commands.first(), Some(&0x01));
+            assert_eq!(c.commands.last(), Some(&0xf3));
+        }
+
+        assert_eq!(caps[0].allowed_values(0xe2).unwrap().len(), 8);
+        assert_eq!(caps[0].vcp_features.len(), 30);
+        assert_eq!(caps[1].protocol.as_deref(), Some("monitor"));
+        assert_eq!(caps[1].allowed_values(0xd6), Some(&[0x01, 0x04][..]));
+        assert_eq!(caps[1].allowed_values(0xed), Some(&[][..]));
+        assert_eq!(caps[2].allowed_values(0xcc).unwrap().len(), 11);
+        assert_eq!(caps[2].mccs_version.unwrap().to_string(), "2.2");
+        assert_eq!(caps[3].allowed_values(0xdc).unwrap().len(), 6);
+        assert_eq!(caps[3].allowed_values(0xfd), Some(&[][..]));
+        assert_eq!(caps[4].protocol.as_deref(), Some("display"));
+        assert_eq!(
+            caps[4].allowed_values(0x14),
+            Some(&[0x05, 0x06, 0x08, 0x0b][..])
+        );
+        assert_eq!(caps[4].allowed_values(0x60), Some(&[0x0f, 0x11, 0x12][..]));
+        assert_eq!(caps[4].mccs_version.unwrap().major, 3);
+        assert_eq!(caps[5].commands, [0x01, 0x02, 0x03, 0x07, 0x0c, 0xe3, 0xf3]);
+        assert_eq!(caps[5].allowed_values(0x62), Some(&[0x00, 0x0f][..]));
+        assert_eq!(
+            caps[5].mccs_version,
+            Some(MccsVersion { major: 2, minor: 2 })
+        );
+    }
+
+    #[test]
+    fn malformed_mccs_version_is_left_out() {
+        for version in ["", "two", "2", "2.x", "300.1"] {
+            let caps = parse(&format!("(vcp(10)mccs_ver({version}))"));
+            assert_eq!(caps.mccs_version, None, "{version:?}");
+            assert!(caps.supports(0x10));
+        }
+    }
+
+    #[test]
+    fn truncated_strings_fail_or_miss_entries() {
+        let full = parse(CORPUS[0]);
+        for (end, _) in CORPUS[0].char_indices() {
+            if let Ok(caps) = CORPUS[0][..end].parse::<Capabilities>() {
+                assert!(caps.vcp_features.len() <= full.vcp_features.len());
+            }
+        }
+        assert!("(prot(monitor)vcp(10 12".parse::<Capabilities>().is_err());
+        assert!("(vcp(10 14(05 08)".parse::<Capabilities>().is_err());
+        assert!("(vcp(10 1".parse::<Capabilities>().is_err());
+    }
+
+    #[test]
+    fn arbitrary_input_does_not_panic() {
+        // Deterministic xorshift generator, so that failures can be reproduced.
+        let mut state = 0x2545_f491_4f6c_dd1d_u64;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+        let alphabet = "()0123456789abcdefABCDEF xyz_.(\0é)"
+            .chars()
+            .collect::<Vec<_>>();
+        for _ in 0..20_000 {
+            let length = (next() % 64) as usize;
+            let chars = (0..length)
+                .map(|_| alphabet[(next() % alphabet.len() as u64) as usize])
+                .collect::<String>();
+            let _ = chars.parse::<Capabilities>();
+
+            let bytes = (0..length).map(|_| next() as u8).collect::<Vec<_>>();
+            let _ = String::from_utf8_lossy(&bytes).parse::<Capabilities>();
+
+            // Corpus strings with a byte changed.
+            let mut mutated = CORPUS[(next() % CORPUS.len() as u64) as usize]
+                .as_bytes()
+                .to_vec();
+            let index = (next() % mutated.len() as u64) as usize;
+            mutated[index] = next() as u8;
+            let _ = String::from_utf8_lossy(&mutated).parse::<Capabilities>();
+        }
+    }
+}
@@ -0,0 +1,122 @@
+// Copyright (C) 2022 The brightness project authors. Distributed under the 0BSD license.
+
+//! Conversions between percentages and raw device values.
+//!
+//! Both directions round to the nearest value, halves rounding up. With a raw range of at least
+//! 100 steps, every percentage survives a round trip (`set(p)` followed by `get()` returns `p`).
+//! With fewer steps, not every percentage can be represented, but raw values survive a round trip
+//! instead. Either way, repeating `set(get())` cannot make the brightness drift.
+
+use crate::{Level, Perception};
+use std::cmp::Ordering;
+
+/// Percentage points by which brightness keys change brightness.
+pub(crate) const STEP: i32 = 5;
+
+/// Converts a raw value in `0..=max` to a percentage. Values above `max` are clamped.
+pub(crate) fn raw_to_percentage(raw: u32, max: u32) -> u32 {
+    if max == 0 {
+        return 0;
+    }
+    scale_ratio(raw.min(max).into(), max.into(), 100)
+}
+
+/// Converts a percentage to a raw value in `0..=max`. Percentages above 100 are clamped.
+pub(crate) fn percentage_to_raw(percentage: u32, max: u32) -> u32 {
+    scale_ratio(percentage.min(100).into(), 100, max)
+}
+
+/// Scales the ratio `numerator / denominator`, between 0 and 1, to `0..=scale`, rounding to the
+/// nearest value, halves rounding up.
+pub(crate) fn scale_ratio(numerator: u64, denominator: u64, scale: u32) -> u32 {
+    let numerator = u128::from(numerator) * u128::from(scale) * 2 + u128::from(denominator);
+    (numerator / (u128::from(denominator) * 2)) as u32
+}
+
+/// Returns the raw value reached by changing `raw` by `delta` percentage points, on the perceived
+/// scale given by `correction` if any.
+///
+/// The value moves by at least one raw step in the direction of `delta`, never moves the other way,
+/// and doesn't go past `bounds`, a value already past them staying where it is.
+pub(crate) fn adjusted_raw(
+    raw: u32,
+    max: u32,
+    delta: i32,
+    bounds: (Level, Level),
+    correction: Option<Perception>,
+) -> u32 {
+    let to_raw = |level| correction.map_or(level, |p| p.linearize(level)).to_raw(max);
+    let raw = raw.min(max);
+    let current = Level::from_raw_clamped(raw, max);
+    let current = correction.map_or(current, |p| p.perceive(current));
+    let target = Level::from_fraction_clamped(current.fraction() + f64::from(delta) / 100.0);
+    let target = to_raw(target);
+    let (low, high) = (to_raw(bounds.0), to_raw(bounds.1));
+    match delta.cmp(&0) {
+        Ordering::Greater => target.max(raw.saturating_add(1)).min(high).max(raw),
+        Ordering::Less => target.min(raw.saturating_sub(1)).max(low).min(raw),
+        Ordering::Equal => raw,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Large maximum raw values checked in addition to every maximum up to 100000.
+    const LARGE_MAX: [u32; 4] = [1 << 20, 96000 * 97, u32::MAX / 2, u32::MAX];
+
+    fn all_max() -> impl Iterator<Item = u32> {
+        (1..=100_000).chain(LARGE_MAX)
+    }
+
+    #[test]
+    fn percentages_round_trip_with_100_steps_or_more() {
+        for max in all_max().filter(|&max| max >= 100) {
+            for percentage in 0..=100 {
+                let raw = percentage_to_raw(percentage, max);
+                assert_eq!(raw_to_percentage(raw, max), percentage, "max {max}");
+            }
+        }
+    }
+
+    #[test]
+    fn raw_values_round_trip_with_fewer_than_100_steps() {
+        for max in 1..100 {
+            for raw in 0..=max {
+                let percentage = raw_to_percentage(raw, max);
+                assert_eq!(percentage_to_raw(percentage, max), raw, "max {max}");
+            }
+        }
+    }
+
+    #[test]
+    fn repeating_set_get_does_not_drift() {
+        for max in all_max() {
+            for percentage in 0..=100 {
+                let raw = percentage_to_raw(percentage, max);
+                let again = percentage_to_raw(raw_to_percentage(raw, max), max);
+                assert_eq!(again, raw, "max {max}, percentage {percentage}");
+            }
+        }
+    }
+
+    #[test]
+    fn conversions_stay_in_range() {
+        for max in all_max() {
+            assert_eq!(percentage_to_raw(0, max), 0);
+            assert_eq!(percentage_to_raw(100, max), max);
+            assert_eq!(percentage_to_raw(150, max), max);
+            assert_eq!(raw_to_percentage(max, max), 100);
+            assert_eq!(raw_to_percentage(max.saturating_add(1), max), 100);
+        }
+    }
+
+    #[test]
+    fn zero_max_is_always_off() {
+        for value in [0, 1, 50, 100, u32::MAX] {
+            assert_eq!(raw_to_percentage(value, 0), 0);
+            assert_eq!(percentage_to_raw(value, 0), 0);
+        }
+    }
+}
@@ -0,0 +1,401 @@
+// Copyright (C) 2022 The brightness project authors. Distributed under the 0BSD license.
+
+//! DDC/CI protocol used to control monitors over I2C, as specified by VESA MCCS.
+
+use crate::VcpValue;
+use std::{
+    io, thread,
+    time::{Duration, Instant},
+};
+use thiserror::Error;
+
+/// I2C address of the DDC/CI interface of a monitor.
+pub(crate) const DDC_ADDRESS: u16 = 0x37;
+
+/// Address of the monitor used in checksums of messages sent to it.
+const DISPLAY_ADDRESS: u8 = 0x6e;
+/// Source address of messages sent by the host.
+const HOST_ADDRESS: u8 = 0x51;
+/// Address of the host used in checksums of messages received from the monitor.
+const VIRTUAL_HOST_ADDRESS: u8 = 0x50;
+
+const GET_VCP_REQUEST: u8 = 0x01;
+const GET_VCP_REPLY: u8 = 0x02;
+const SET_VCP_REQUEST: u8 = 0x03;
+const CAPABILITIES_REQUEST: u8 = 0xf3;
+const CAPABILITIES_REPLY: u8 = 0xe3;
+
+/// Maximum number of bytes of a capabilities string fragment.
+const CAPABILITIES_FRAGMENT_SIZE: usize = 32;
+/// Length beyond which capabilities strings are assumed to be garbage from a faulty monitor.
+const MAX_CAPABILITIES_LENGTH: usize = 8192;
+
+/// Time the monitor needs before the reply to a request can be read.
+const REPLY_DELAY: Duration = Duration::from_millis(40);
+/// Minimum time between the end of a command and the start of the next one.
+const COMMAND_INTERVAL: Duration = Duration::from_millis(50);
+/// Number of attempts of a command before giving up.
+const ATTEMPTS: u32 = 3;
+
+/// Raw access to the I2C bus of a monitor, addressing its DDC/CI interface.
+pub(crate) trait I2cTransport {
+    /// Writes a message.
+    fn write(&mut self, data: &[u8]) -> io::Result<()>;
+
+    /// Reads a message, filling `buffer`.
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<()>;
+}
+
+/// DDC/CI error.
+#[derive(Debug, Error)]
+pub(crate) enum DdcError {
+    #[error("I2C communication failed")]
+    Io(#[from] io::Error),
+    #[error("Invalid DDC/CI reply checksum")]
+    Checksum,
+    /// The monitor replied with a null message, i.e. it could not answer in time.
+    #[error("Display did not reply")]
+    NullReply,
+    #[error("Invalid DDC/CI reply")]
+    InvalidReply,
+    #[error("Display does not support VCP feature {0:#04x}")]
+    UnsupportedFeature(u8),
+}
+
+impl DdcError {
+    /// Returns whether the command may succeed if attempted again.
+    fn is_transient(&self) -> bool {
+        !matches!(self, DdcError::UnsupportedFeature(_))
+    }
+}
+
+/// DDC/CI connection to a monitor.
+///
+/// Commands are spaced as required by the specification and attempted several times, as monitors
+/// commonly fail to answer or corrupt replies.
+#[derive(Debug)]
+pub(crate) struct Ddc<T> {
+    transport: T,
+    last_command: Option<Instant>,
+    reply_delay: Duration,
+    command_interval: Duration,
+}
+
+impl<T: I2cTransport> Ddc<T> {
+    pub(crate) fn new(transport: T) -> Self {
+        Self {
+            transport,
+            last_command: None,
+            reply_delay: REPLY_DELAY,
+            command_interval: COMMAND_INTERVAL,
+        }
+    }
+
+    /// Returns the value of a VCP feature.
+    pub(crate) fn get_vcp(&mut self, code: u8) -> Result<VcpValue, DdcError> {
+        self.retry(|ddc| {
+            ddc.send(&[GET_VCP_REQUEST, code])?;
+            thread::sleep(ddc.reply_delay);
+            let mut reply = [0; 11];
+            match ddc.receive(&mut reply)? {
+                [GET_VCP_REPLY, 0, c, _, max_hi, max_lo, hi, lo] if *c == code => Ok(VcpValue {
+                    current: u16::from_be_bytes([*hi, *lo]),
+                    max: u16::from_be_bytes([*max_hi, *max_lo]),
+                }),
+                [GET_VCP_REPLY, 1, ..] => Err(DdcError::UnsupportedFeature(code)),
+                _ => Err(DdcError::InvalidReply),
+            }
+        })
+    }
+
+    /// Sets the value of a VCP feature.
+    pub(crate) fn set_vcp(&mut self, code: u8, value: u16) -> Result<(), DdcError> {
+        let [hi, lo] = value.to_be_bytes();
+        self.retry(|ddc| ddc.send(&[SET_VCP_REQUEST, code, hi, lo]))
+    }
+
+    /// Returns the capabilities string, read in fragments.
+    pub(crate) fn capabilities(&mut self) -> Result<String, DdcError> {
+        let mut capabilities = Vec::new();
+        loop {
+            let offset = capabilities.len();
+            let fragment = self.retry(|ddc| ddc.capabilities_fragment(offset))?;
+            if fragment.is_empty() {
+                break;
+            }
+            capabilities.extend_from_slice(&fragment);
+            if capabilities.len() > MAX_CAPABILITIES_LENGTH {
+                return Err(DdcError::InvalidReply);
+            }
+        }
+        // The string is sometimes terminated by a null byte.
+        let end = capabilities
+            .iter()
+            .position(|&b| b == 0)
+            .unwrap_or(capabilities.len());
+        Ok(String::from_utf8_lossy(&capabilities[..end]).into_owned())
+    }
+
+    /// Returns the fragment of the capabilities string at the given offset. The fragment is empty
+    /// at the end of the string.
+    fn capabilities_fragment(&mut self, offset: usize) -> Result<Vec<u8>, DdcError> {
+        let [hi, lo] = u16::try_from(offset)
+            .map_err(|_| DdcError::InvalidReply)?
+            .to_be_bytes();
+        self.send(&[CAPABILITIES_REQUEST, hi, lo])?;
+        thread::sleep(self.reply_delay);
+        let mut reply = [0; CAPABILITIES_FRAGMENT_SIZE + 6];
+        match self.receive(&mut reply)? {
+            [CAPABILITIES_REPLY, h, l, data @ ..] if [*h, *l] == [hi, lo] => Ok(data.to_vec()),
+            _ => Err(DdcError::InvalidReply),
+        }
+    }
+
+    fn retry<R>(&mut self, f: impl Fn(&mut Self) -> Result<R, DdcError>) -> Result<R, DdcError> {
+        let mut attempt = 1;
+        loop {
+            match f(self) {
+                Err(e) if e.is_transient() && attempt < ATTEMPTS => attempt += 1,
+                result => return result,
+            }
+        }
+    }
+
+    /// Sends a message made of the given payload, waiting for the previous command to be
+    /// processed.
+    fn send(&mut self, payload: &[u8]) -> Result<(), DdcError> {
+        if let Some(last) = self.last_command {
+            thread::sleep(self.command_interval.saturating_sub(last.elapsed()));
+        }
+        let mut message = Vec::with_capacity(payload.len() + 3);
+        message.push(HOST_ADDRESS);
+        message.push(0x80 | payload.len() as u8);
+        message.extend_from_slice(payload);
+        message.push(checksum(DISPLAY_ADDRESS, &message));
+        let result = self.transport.write(&message);
+        self.last_command = Some(Instant::now());
+        Ok(result?)
+    }
+
+    /// Receives a reply filling `reply`, checking its length and checksum. Returns the payload.
+    fn receive<'a>(&mut self, reply: &'a mut [u8]) -> Result<&'a [u8], DdcError> {
+        let result = self.transport.read(reply);
+        self.last_command = Some(Instant::now());
+        result?;
+        let length = usize::from(reply[1] & 0x7f);
+        if length == 0 {
+            return Err(DdcError::NullReply);
+        }
+        if reply[1] & 0x80 == 0 || length + 3 > reply.len() {
+            return Err(DdcError::InvalidReply);
+        }
+        let (message, rest) = reply.split_at(length + 2);
+        if checksum(VIRTUAL_HOST_ADDRESS, message) != rest[0] {
+            return Err(DdcError::Checksum);
+        }
+        Ok(&message[2..])
+    }
+}
+
+fn checksum(address: u8, message: &[u8]) -> u8 {
+    message.iter().fold(address, |acc, b| acc ^ b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+
+    /// Monitor answering with prepared replies, recording the messages written to it.
+    #[derive(Default)]
+    struct FakeMonitor {
+        written: Vec<Vec<u8>>,
+        replies: VecDeque<Vec<u8>>,
+    }
+
+    impl FakeMonitor {
+        fn replying(replies: impl IntoIterator<Item = Vec<u8>>) -> Ddc<Self> {
+            let monitor = FakeMonitor {
+                written: Vec::new(),
+                replies: replies.into_iter().collect(),
+            };
+            Ddc {
+                reply_delay: Duration::ZERO,
+                command_interval: Duration::ZERO,
+                ..Ddc::new(monitor)
+            }
+        }
+    }
+
+    impl I2cTransport for FakeMonitor {
+        fn write(&mut self, data: &[u8]) -> io::Result<()> {
+            let (message, check) = data.split_at(data.len() - 1);
+            assert_eq!(checksum(DISPLAY_ADDRESS, message), check[0], "{data:02x?}");
+            self.written.push(data.to_vec());
+            Ok(())
+        }
+
+        fn read(&mut self, buffer: &mut [u8]) -> io::Result<()> {
+            let reply = self.replies.pop_front().expect("unexpected read");
+            buffer.fill(0);
+            buffer[..reply.len()].copy_from_slice(&reply);
+            Ok(())
+        }
+    }
+
+    /// Returns a reply of the monitor carrying the given payload.
+    fn reply(payload: &[u8]) -> Vec<u8> {
+        let mut message = vec![DISPLAY_ADDRESS, 0x80 | payload.len() as u8];
+        message.extend_from_slice(payload);
+        message.push(checksum(VIRTUAL_HOST_ADDRESS, &message));
+        message
+    }
+
+    fn null_reply() -> Vec<u8> {
+        reply(&[])
+    }
+
+    fn corrupt(mut reply: Vec<u8>) -> Vec<u8> {
+        *reply.last_mut().unwrap() ^= 0xff;
+        reply
+    }
+
+    fn vcp_reply(code: u8, current: u16, max: u16) -> Vec<u8> {
+        let ([max_hi, max_lo], [hi, lo]) = (max.to_be_bytes(), current.to_be_bytes());
+        reply(&[GET_VCP_REPLY, 0, code, 0, max_hi, max_lo, hi, lo])
+    }
+
+    fn fragment_reply(offset: u16, data: &[u8]) -> Vec<u8> {
+        let [hi, lo] = offset.to_be_bytes();
+        reply(&[&[CAPABILITIES_REPLY, hi, lo], data].concat())
+    }
+
+    /// Returns the replies of a monitor sending `capabilities` in full fragments.
+    fn fragment_replies(capabilities: &[u8]) -> Vec<Vec<u8>> {
+        let mut replies = capabilities
+            .chunks(CAPABILITIES_FRAGMENT_SIZE)
+            .enumerate()
+            .map(|(i, data)| fragment_reply((i * CAPABILITIES_FRAGMENT_SIZE) as u16, data))
+            .collect::<Vec<_>>();
+        replies.push(fragment_reply(capabilities.len() as u16, &[]));
+        replies
+    }
+
+    #[test]
+    fn vcp_feature_is_read() {
+        let mut ddc = FakeMonitor::replying([vcp_reply(0x10, 40, 100)]);
+        let value = ddc.get_vcp(0x10).unwrap();
+        assert_eq!(
+            value,
+            VcpValue {
+                current: 40,
+                max: 100
+            }
+        );
+        // Sent from 0x51 with a checksum including the display address 0x6e.
+        assert_eq!(ddc.transport.written, [[0x51, 0x82, 0x01, 0x10, 0xac]]);
+    }
+
+    #[test]
+    fn vcp_feature_is_set() {
+        let mut ddc = FakeMonitor::replying([]);
+        ddc.set_vcp(0x10, 0x0123).unwrap();
+        assert_eq!(
+            ddc.transport.written,
+            [[0x51, 0x84, 0x03, 0x10, 0x01, 0x23, 0x8a]]
+        );
+    }
+
+    #[test]
+    fn corrupt_replies_are_retried() {
+        let mut ddc =
+            FakeMonitor::replying([corrupt(vcp_reply(0x10, 40, 100)), vcp_reply(0x10, 50, 100)]);
+        assert_eq!(ddc.get_vcp(0x10).unwrap().current, 50);
+        assert_eq!(ddc.transport.written.len(), 2);
+
+        let replies = (0..ATTEMPTS).map(|_| corrupt(vcp_reply(0x10, 40, 100)));
+        let mut ddc = FakeMonitor::replying(replies);
+        assert!(matches!(ddc.get_vcp(0x10), Err(DdcError::Checksum)));
+        assert_eq!(ddc.transport.written.len(), ATTEMPTS as usize);
+    }
+
+    #[test]
+    fn null_replies_are_retried() {
+        let mut ddc = FakeMonitor::replying([null_reply(), null_reply(), vcp_reply(0x10, 60, 100)]);
+        assert_eq!(ddc.get_vcp(0x10).unwrap().current, 60);
+        assert_eq!(ddc.transport.written.len(), 3);
+
+        let mut ddc = FakeMonitor::replying((0..ATTEMPTS).map(|_| null_reply()));
+        assert!(matches!(ddc.get_vcp(0x10), Err(DdcError::NullReply)));
+    }
+
+    #[test]
+    fn unsupported_features_are_not_retried() {
+        let mut ddc = FakeMonitor::replying([reply(&[GET_VCP_REPLY, 1, 0xdf, 0, 0, 0, 0, 0])]);
+        assert!(matches!(
+            ddc.get_vcp(0xdf),
+            Err(DdcError::UnsupportedFeature(0xdf))
+        ));
+        assert_eq!(ddc.transport.written.len(), 1);
+    }
+
+    #[test]
+    fn replies_for_other_features_are_invalid() {
+        let replies = (0..ATTEMPTS).map(|_| vcp_reply(0x12, 40, 100));
+        let mut ddc = FakeMonitor::replying(replies);
+        assert!(matches!(ddc.get_vcp(0x10), Err(DdcError::InvalidReply)));
+    }
+
+    #[test]
+    fn capabilities_are_read_in_fragments() {
+        let capabilities =
+            b"(prot(monitor)type(lcd)model(X27)cmds(01 02 03 f3)vcp(10 12 60(0f 11))mccs_ver(2.2))";
+        let mut ddc = FakeMonitor::replying(fragment_replies(capabilities));
+        assert_eq!(ddc.capabilities().unwrap().as_bytes(), capabilities);
+        let offsets = ddc
+            .transport
+            .written
+            .iter()
+            .map(|message| {
+                assert_eq!(message[..3], [0x51, 0x83, CAPABILITIES_REQUEST]);
+                u16::from_be_bytes([message[3], message[4]])
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(offsets, [0, 32, 64, 84]);
+    }
+
+    #[test]
+    fn capabilities_fragments_are_retried() {
+        let mut replies = fragment_replies(b"(prot(monitor)type(lcd)model(X27)vcp(10))");
+        // A fragment for the wrong offset, then no reply, before the right one.
+        replies.insert(1, fragment_reply(0, b"(prot(monitor)"));
+        replies.insert(2, null_reply());
+        let mut ddc = FakeMonitor::replying(replies);
+        assert_eq!(
+            ddc.capabilities().unwrap(),
+            "(prot(monitor)type(lcd)model(X27)vcp(10))"
+        );
+        let offsets = ddc
+            .transport
+            .written
+            .iter()
+            .map(|m| m[4])
+            .collect::<Vec<_>>();
+        assert_eq!(offsets, [0, 32, 32, 32, 41]);
+    }
+
+    #[test]
+    fn capabilities_end_at_a_null_byte() {
+        let mut ddc = FakeMonitor::replying(fragment_replies(b"(vcp(10))\0\xff\xff"));
+        assert_eq!(ddc.capabilities().unwrap(), "(vcp(10))");
+    }
+
+    #[test]
+    fn endless_capabilities_are_invalid() {
+        let garbage = vec![b'x'; MAX_CAPABILITIES_LENGTH + CAPABILITIES_FRAGMENT_SIZE];
+        let mut ddc = FakeMonitor::replying(fragment_replies(&garbage));
+        assert!(matches!(ddc.capabilities(), Err(DdcError::InvalidReply)));
+        let fragments = MAX_CAPABILITIES_LENGTH / CAPABILITIES_FRAGMENT_SIZE + 1;
+        assert_eq!(ddc.transport.written.len(), fragments);
+    }
+}
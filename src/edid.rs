@@ -0,0 +1,227 @@
+// Copyright (C) 2022 The brightness project authors. Distributed under the 0BSD license.
+
+//! Identification of displays from their EDID.
+
+use crate::Error;
+
+const HEADER: [u8; 8] = [0x00, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00];
+/// Size of the base EDID block.
+const BLOCK_SIZE: usize = 128;
+/// Offsets of the four 18-byte descriptors of the base block.
+const DESCRIPTORS: [usize; 4] = [54, 72, 90, 108];
+const SERIAL_DESCRIPTOR: u8 = 0xff;
+const NAME_DESCRIPTOR: u8 = 0xfc;
+/// Week value meaning that the year is the model year instead of the year of manufacture.
+const MODEL_YEAR_WEEK: u8 = 0xff;
+
+/// Identity of a display, decoded from its EDID.
+///
+/// Unlike device names, this identifies the same display across reboots and connections.
+///
+/// # Example
+///
+/// ```rust
+/// use brightness::DisplayInfo;
+///
+/// let hex = "00ffffffffffff0010acbca04c3031300a1a0104a53420783a00000000000000\
+///            00000000000000000000000000000000000000000000283c80a070b023403020\
+///            360006442100001a000000ff00374d5430313637423056394c0a000000fc0044\
+///            454c4c2055323431350a2020000000fd00384c1e5111000a20202020202000bc";
+/// let edid = (0..hex.len())
+///     .step_by(2)
+///     .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+///     .collect::<Vec<_>>();
+/// let info = DisplayInfo::from_edid(&edid).unwrap();
+/// assert_eq!(info.manufacturer_id, "DEL");
+/// assert_eq!(info.product_code, 0xa0bc);
+/// assert_eq!(info.serial_number, Some(0x3031304c));
+/// assert_eq!(info.serial.as_deref(), Some("7MT0167B0V9L"));
+/// assert_eq!(info.model.as_deref(), Some("DELL U2415"));
+/// assert_eq!((info.manufacture_year, info.manufacture_week), (Some(2016), Some(10)));
+///
+/// assert!(DisplayInfo::from_edid(&edid[..100]).is_err());
+/// ```
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct DisplayInfo {
+    /// Three-letter PNP ID of the manufacturer, e.g. `DEL`.
+    pub manufacturer_id: String,
+    /// Product code assigned by the manufacturer.
+    pub product_code: u16,
+    /// Numeric serial number, if set.
+    pub serial_number: Option<u32>,
+    /// Serial number string, if set. Displays often set this instead of the numeric serial number.
+    pub serial: Option<String>,
+    /// Model name, if set.
+    pub model: Option<String>,
+    /// Year of manufacture, if known.
+    pub manufacture_year: Option<u16>,
+    /// Week of manufacture, from 1 to 54, if known.
+    pub manufacture_week: Option<u8>,
+    /// Model year, for displays that report it instead of the date of manufacture.
+    pub model_year: Option<u16>,
+}
+
+impl DisplayInfo {
+    /// Decodes the base block of an EDID. Extension blocks are ignored.
+    pub fn from_edid(edid: &[u8]) -> Result<Self, Error> {
+        if edid.len() < BLOCK_SIZE {
+            return Err(invalid(format!(
+                "EDID is {} bytes long instead of at least {BLOCK_SIZE}",
+                edid.len()
+            )));
+        }
+        if edid[..HEADER.len()] != HEADER {
+            return Err(invalid("invalid EDID header".into()));
+        }
+        // The bytes of the block sum to zero.
+        let sum = edid[..BLOCK_SIZE]
+            .iter()
+            .fold(0_u8, |sum, &b| sum.wrapping_add(b));
+        if sum != 0 {
+            return Err(invalid("invalid EDID checksum".into()));
+        }
+        // Five bits per letter, 1 standing for 'A'.
+        let id = u16::from_be_bytes([edid[8], edid[9]]);
+        let manufacturer_id = [10, 5, 0]
+            .into_iter()
+            .map(|shift| char::from(b'A' - 1 + ((id >> shift) & 0x1f) as u8))
+            .collect();
+        let serial_number = u32::from_le_bytes([edid[12], edid[13], edid[14], edid[15]]);
+        let (week, year) = (edid[16], 1990 + u16::from(edid[17]));
+        let (manufacture_week, manufacture_year, model_year) = match week {
+            MODEL_YEAR_WEEK => (None, None, Some(year)),
+            0 => (None, Some(year), None),
+            week => (Some(week), Some(year), None),
+        };
+        Ok(DisplayInfo {
+            manufacturer_id,
+            product_code: u16::from_le_bytes([edid[10], edid[11]]),
+            serial_number: (serial_number != 0).then_some(serial_number),
+            serial: descriptor_text(edid, SERIAL_DESCRIPTOR),
+            model: descriptor_text(edid, NAME_DESCRIPTOR),
+            manufacture_year,
+            manufacture_week,
+            model_year,
+        })
+    }
+}
+
+/// Returns the text of the first display descriptor with the given tag.
+///
+/// Text is up to 13 bytes, terminated by a line feed and padded with spaces.
+fn descriptor_text(edid: &[u8], tag: u8) -> Option<String> {
+    DESCRIPTORS.iter().find_map(|&offset| {
+        let descriptor = &edid[offset..offset + 18];
+        if descriptor[..3] != [0, 0, 0] || descriptor[3] != tag {
+            return None;
+        }
+        let text = &descriptor[5..];
+        let end = text.iter().position(|&b| b == b'\n').unwrap_or(text.len());
+        let text = String::from_utf8_lossy(&text[..end]).trim().to_owned();
+        (!text.is_empty()).then_some(text)
+    })
+}
+
+fn invalid(reason: String) -> Error {
+    Error::InvalidEdid(reason)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// EDID of a DELL U2415.
+    const EDID: &str = "00ffffffffffff0010acbca04c3031300a1a0104a53420783a00000000000000\
+                        00000000000000000000000000000000000000000000283c80a070b023403020\
+                        360006442100001a000000ff00374d5430313637423056394c0a000000fc0044\
+                        454c4c2055323431350a2020000000fd00384c1e5111000a20202020202000bc";
+
+    fn edid() -> Vec<u8> {
+        (0..EDID.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&EDID[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    /// Returns the EDID after `change`, with its checksum fixed.
+    fn edid_with(change: impl FnOnce(&mut [u8])) -> Vec<u8> {
+        let mut edid = edid();
+        change(&mut edid);
+        let sum = edid[..BLOCK_SIZE - 1]
+            .iter()
+            .fold(0_u8, |sum, &b| sum.wrapping_add(b));
+        edid[BLOCK_SIZE - 1] = sum.wrapping_neg();
+        edid
+    }
+
+    #[test]
+    fn invalid_header_is_rejected() {
+        let edid = edid_with(|edid| edid[0] = 0xff);
+        assert!(matches!(
+            DisplayInfo::from_edid(&edid),
+            Err(Error::InvalidEdid(_))
+        ));
+    }
+
+    #[test]
+    fn invalid_checksum_is_rejected() {
+        let mut edid = edid();
+        edid[BLOCK_SIZE - 1] ^= 1;
+        assert!(matches!(
+            DisplayInfo::from_edid(&edid),
+            Err(Error::InvalidEdid(_))
+        ));
+    }
+
+    #[test]
+    fn truncated_edid_is_rejected() {
+        let edid = edid();
+        for len in [0, HEADER.len(), BLOCK_SIZE - 1] {
+            assert!(matches!(
+                DisplayInfo::from_edid(&edid[..len]),
+                Err(Error::InvalidEdid(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn extension_blocks_are_ignored() {
+        let mut edid = edid();
+        edid.extend([0xaa; BLOCK_SIZE]);
+        let info = DisplayInfo::from_edid(&edid).unwrap();
+        assert_eq!(info.model.as_deref(), Some("DELL U2415"));
+    }
+
+    #[test]
+    fn week_0xff_means_model_year() {
+        let edid = edid_with(|edid| edid[16] = MODEL_YEAR_WEEK);
+        let info = DisplayInfo::from_edid(&edid).unwrap();
+        assert_eq!(info.model_year, Some(2016));
+        assert_eq!((info.manufacture_year, info.manufacture_week), (None, None));
+    }
+
+    #[test]
+    fn week_0_means_unknown_week() {
+        let edid = edid_with(|edid| edid[16] = 0);
+        let info = DisplayInfo::from_edid(&edid).unwrap();
+        assert_eq!(info.manufacture_year, Some(2016));
+        assert_eq!((info.manufacture_week, info.model_year), (None, None));
+    }
+
+    #[test]
+    fn missing_descriptors_are_none() {
+        let edid = edid_with(|edid| {
+            for offset in DESCRIPTORS {
+                // Dummy descriptors.
+                edid[offset..offset + 18].fill(0);
+                edid[offset + 3] = 0x10;
+            }
+            edid[12..16].fill(0);
+        });
+        let info = DisplayInfo::from_edid(&edid).unwrap();
+        assert_eq!(info.model, None);
+        assert_eq!(info.serial, None);
+        assert_eq!(info.serial_number, None);
+        assert_eq!(info.manufacturer_id, "DEL");
+    }
+}
@@ -0,0 +1,162 @@
+// Copyright (C) 2022 The brightness project authors. Distributed under the 0BSD license.
+
+//! Minimum brightness written to devices.
+
+use crate::{DeviceType, Level};
+use std::sync::{Mutex, PoisonError};
+
+/// Floor applied by systemd-backlight to raw and platform devices, which may turn the panel off
+/// at 0.
+const DEFAULT_FLOOR: f64 = 0.05;
+
+static GLOBAL_MIN_LEVEL: Mutex<Option<Level>> = Mutex::new(None);
+
+/// Returns the minimum level set with [`set_global_min_level`], if any.
+pub fn global_min_level() -> Option<Level> {
+    *GLOBAL_MIN_LEVEL
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Sets the minimum level of all devices that don't have their own, or restores the default with
+/// `None`.
+///
+/// By default, devices of type [`DeviceType::Raw`] and [`DeviceType::Platform`] don't go below 5%
+/// of their maximum brightness, like systemd-backlight does, as some of them turn the panel off at
+/// 0. Other devices can be set to 0. [`Level::MIN`] removes the floor.
+///
+/// # Example
+///
+/// ```rust
+/// use brightness::Level;
+///
+/// brightness::set_global_min_level(Some(Level::MIN));
+/// assert_eq!(brightness::global_min_level(), Some(Level::MIN));
+/// brightness::set_global_min_level(None);
+/// ```
+pub fn set_global_min_level(level: Option<Level>) {
+    *GLOBAL_MIN_LEVEL
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner) = level;
+}
+
+/// Returns the minimum level of a device given its own minimum level, if set, and its type.
+pub(crate) fn min_level(device: Option<Level>, device_type: DeviceType) -> Level {
+    device
+        .or_else(global_min_level)
+        .unwrap_or(match device_type {
+            DeviceType::Raw | DeviceType::Platform => Level::from_fraction_clamped(DEFAULT_FLOOR),
+            DeviceType::Firmware | DeviceType::Unknown => Level::MIN,
+        })
+}
+
+/// Returns the lowest raw value in `0..=max` allowed by `min`, at least 1 if `min` is not
+/// [`Level::MIN`].
+pub(crate) fn min_raw(min: Level, max: u32) -> u32 {
+    if min == Level::MIN {
+        return 0;
+    }
+    min.to_raw(max).max(1).min(max)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::MutexGuard;
+
+    /// Serializes tests that depend on the global minimum level.
+    static GLOBAL_MIN_LEVEL_TESTS: Mutex<()> = Mutex::new(());
+
+    /// Restores the default global minimum level when dropped.
+    struct GlobalMinLevel {
+        _lock: MutexGuard<'static, ()>,
+    }
+
+    impl GlobalMinLevel {
+        fn lock() -> Self {
+            let lock = GLOBAL_MIN_LEVEL_TESTS.lock();
+            Self {
+                _lock: lock.unwrap_or_else(PoisonError::into_inner),
+            }
+        }
+    }
+
+    impl Drop for GlobalMinLevel {
+        fn drop(&mut self) {
+            set_global_min_level(None);
+        }
+    }
+
+    fn level(percentage: u32) -> Level {
+        Level::from_percentage(percentage).unwrap()
+    }
+
+    #[test]
+    fn raw_and_platform_devices_have_a_default_floor() {
+        let _global = GlobalMinLevel::lock();
+        for device_type in [DeviceType::Raw, DeviceType::Platform] {
+            assert_eq!(min_level(None, device_type).to_raw(1000), 50);
+        }
+        for device_type in [DeviceType::Firmware, DeviceType::Unknown] {
+            assert_eq!(min_level(None, device_type), Level::MIN);
+        }
+    }
+
+    #[test]
+    fn device_min_level_overrides_the_default() {
+        let _global = GlobalMinLevel::lock();
+        assert_eq!(min_level(Some(level(20)), DeviceType::Firmware), level(20));
+        assert_eq!(min_level(Some(Level::MIN), DeviceType::Raw), Level::MIN);
+    }
+
+    #[test]
+    fn global_min_level_applies_to_devices_without_their_own() {
+        let _global = GlobalMinLevel::lock();
+        set_global_min_level(Some(Level::MIN));
+        assert_eq!(global_min_level(), Some(Level::MIN));
+        assert_eq!(min_level(None, DeviceType::Raw), Level::MIN);
+        assert_eq!(min_level(Some(level(20)), DeviceType::Raw), level(20));
+        set_global_min_level(None);
+        assert_eq!(min_level(None, DeviceType::Raw).to_raw(1000), 50);
+    }
+
+    #[test]
+    fn min_raw_is_at_least_one_step() {
+        assert_eq!(min_raw(Level::MIN, 1000), 0);
+        assert_eq!(min_raw(level(5), 1000), 50);
+        assert_eq!(min_raw(level(5), 7), 1);
+        assert_eq!(min_raw(level(5), 0), 0);
+        assert_eq!(min_raw(Level::MAX, 7), 7);
+    }
+
+    #[cfg(feature = "mock")]
+    #[test]
+    fn levels_are_clamped_to_the_floor() {
+        use crate::{blocking::Brightness, mock::MockBackend};
+
+        let _global = GlobalMinLevel::lock();
+        let backend = MockBackend::new()
+            .with_device("raw", 1000)
+            .with_device("firmware", 1000);
+        backend.set_device_type("raw", DeviceType::Raw);
+        backend.set_device_type("firmware", DeviceType::Firmware);
+        let mut devices = backend.blocking_brightness_devices().map(Result::unwrap);
+        let (mut raw, firmware) = (devices.next().unwrap(), devices.next().unwrap());
+
+        raw.set_level(Level::MIN).unwrap();
+        assert_eq!(backend.value("raw"), Some(50));
+        raw.set(0).unwrap();
+        assert_eq!(backend.value("raw"), Some(50));
+        firmware.set_level(Level::MIN).unwrap();
+        assert_eq!(backend.value("firmware"), Some(0));
+
+        raw.set_min_level(Some(level(10)));
+        raw.set_level(level(2)).unwrap();
+        assert_eq!(backend.value("raw"), Some(100));
+        raw.set_level(level(30)).unwrap();
+        assert_eq!(backend.value("raw"), Some(300));
+        raw.set_min_level(Some(Level::MIN));
+        raw.set_level(Level::MIN).unwrap();
+        assert_eq!(backend.value("raw"), Some(0));
+    }
+}
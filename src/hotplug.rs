@@ -0,0 +1,189 @@
+// Copyright (C) 2022 The brightness project authors. Distributed under the 0BSD license.
+
+//! Notifications of devices appearing and disappearing.
+
+use crate::{
+    Error,
+    blocking::{self, Brightness},
+    watch::Watcher,
+};
+use std::{
+    collections::{HashSet, VecDeque},
+    iter::from_fn,
+};
+
+/// Change in the set of brightness devices.
+#[derive(Debug)]
+pub enum DeviceEvent<D> {
+    /// A device appeared.
+    Added(D),
+    /// The device with the given name disappeared.
+    Removed(String),
+}
+
+impl<D> DeviceEvent<D> {
+    #[cfg(feature = "async")]
+    pub(crate) fn map<E, F: FnOnce(D) -> E>(self, f: F) -> DeviceEvent<E> {
+        match self {
+            DeviceEvent::Added(d) => DeviceEvent::Added(f(d)),
+            DeviceEvent::Removed(name) => DeviceEvent::Removed(name),
+        }
+    }
+}
+
+/// Where devices are enumerated from.
+#[derive(Clone, Debug)]
+pub(crate) enum Source {
+    #[cfg(target_os = "linux")]
+    Sysfs(blocking::linux::Context),
+    #[cfg(windows)]
+    System,
+    #[cfg(feature = "mock")]
+    Mock(crate::mock::MockBackend),
+}
+
+impl Source {
+    fn devices(&self) -> Vec<Result<blocking::BrightnessDevice, Error>> {
+        match self {
+            #[cfg(target_os = "linux")]
+            Source::Sysfs(context) => context.blocking_brightness_devices().collect(),
+            #[cfg(windows)]
+            Source::System => blocking::brightness_devices().collect(),
+            #[cfg(feature = "mock")]
+            Source::Mock(backend) => backend.blocking_brightness_devices().collect(),
+        }
+    }
+
+    fn watcher(&self) -> Result<Watcher, Error> {
+        match self {
+            #[cfg(target_os = "linux")]
+            Source::Sysfs(context) => Ok(Watcher::Directory(
+                blocking::linux::DirectoryWatcher::new(context),
+            )),
+            #[cfg(windows)]
+            Source::System => Ok(Watcher::Interval),
+            #[cfg(feature = "mock")]
+            Source::Mock(backend) => Ok(Watcher::Mock(backend.watcher())),
+        }
+    }
+}
+
+/// Turns successive enumerations into events.
+#[derive(Debug, Default)]
+struct Tracker {
+    known: HashSet<String>,
+}
+
+impl Tracker {
+    fn update(
+        &mut self,
+        devices: Vec<Result<blocking::BrightnessDevice, Error>>,
+    ) -> Vec<Result<DeviceEvent<blocking::BrightnessDevice>, Error>> {
+        let mut events = Vec::new();
+        let mut present = HashSet::new();
+        for device in devices {
+            let device = device.and_then(|d| Ok((d.device_name()?, d)));
+            match device {
+                Ok((name, device)) => {
+                    if !self.known.contains(&name) {
+                        events.push(Ok(DeviceEvent::Added(device)));
+                    }
+                    present.insert(name);
+                }
+                // Keep the known devices if the enumeration itself failed.
+                Err(e @ Error::ListingDevices(_)) => return vec![Err(e)],
+                Err(e) => events.push(Err(e)),
+            }
+        }
+        let mut removed = self.known.difference(&present).cloned().collect::<Vec<_>>();
+        removed.sort();
+        events.extend(
+            removed
+                .into_iter()
+                .map(|name| Ok(DeviceEvent::Removed(name))),
+        );
+        self.known = present;
+        events
+    }
+}
+
+/// Returns an iterator over device events, starting with an event for each existing device.
+pub(crate) fn blocking_events(
+    source: Source,
+) -> impl Iterator<Item = Result<DeviceEvent<blocking::BrightnessDevice>, Error>> {
+    let mut watcher = None;
+    let mut tracker = Tracker::default();
+    let mut pending = VecDeque::new();
+    from_fn(move || {
+        loop {
+            if let Some(event) = pending.pop_front() {
+                return Some(event);
+            }
+            match &mut watcher {
+                None => match source.watcher() {
+                    Ok(w) => watcher = Some(w),
+                    Err(e) => return Some(Err(e)),
+                },
+                Some(w) => {
+                    if let Err(e) = w.wait() {
+                        return Some(Err(e));
+                    }
+                }
+            }
+            pending.extend(tracker.update(source.devices()));
+        }
+    })
+}
+
+/// Returns a stream of device events, starting with an event for each existing device.
+#[cfg(feature = "async")]
+pub(crate) fn events(
+    source: Source,
+) -> impl futures::Stream<Item = Result<DeviceEvent<crate::BrightnessDevice>, Error>> {
+    use ::blocking::unblock;
+    use futures::stream;
+
+    struct State {
+        source: Source,
+        watcher: Option<Watcher>,
+        tracker: Tracker,
+        pending: VecDeque<Result<DeviceEvent<blocking::BrightnessDevice>, Error>>,
+    }
+
+    let state = State {
+        source,
+        watcher: None,
+        tracker: Tracker::default(),
+        pending: VecDeque::new(),
+    };
+    stream::unfold(state, |mut state| async move {
+        loop {
+            if let Some(event) = state.pending.pop_front() {
+                let event = event.map(|e| e.map(crate::BrightnessDevice::from_blocking));
+                return Some((event, state));
+            }
+            // Waiting and enumerating block, but are bounded by the polling interval.
+            state = unblock(move || {
+                match &mut state.watcher {
+                    None => match state.source.watcher() {
+                        Ok(w) => state.watcher = Some(w),
+                        Err(e) => {
+                            state.pending.push_back(Err(e));
+                            return state;
+                        }
+                    },
+                    Some(w) => {
+                        if let Err(e) = w.wait() {
+                            state.pending.push_back(Err(e));
+                            return state;
+                        }
+                    }
+                }
+                let events = state.tracker.update(state.source.devices());
+                state.pending.extend(events);
+                state
+            })
+            .await;
+        }
+    })
+}
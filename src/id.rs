@@ -0,0 +1,112 @@
+// Copyright (C) 2022 The brightness project authors. Distributed under the 0BSD license.
+
+//! Stable device identifiers.
+
+use crate::{DisplayInfo, Error};
+use std::{fmt, str::FromStr};
+
+/// Identifier of a brightness device that stays the same across reboots and enumerations, e.g. to
+/// save preferences per display.
+///
+/// External displays that report a serial number are identified by the manufacturer, product code
+/// and serial number from their EDID, so that a display keeps its identifier whichever driver
+/// controls it, e.g. when the ddcci driver is loaded or unloaded. Other devices are identified by
+/// their driver and device path: the sysfs path of the device on Linux, without the `cardN`
+/// numbers of graphics cards as these depend on the order in which the cards were probed, and the
+/// device path on Windows. The format is meant to be stored and parsed back, not interpreted.
+///
+/// # Example
+///
+/// ```rust
+/// use brightness::DeviceId;
+///
+/// let id: DeviceId = "edid@DEL-A0BC-7MT0167B0V9L".parse().unwrap();
+/// assert_eq!(id.to_string(), "edid@DEL-A0BC-7MT0167B0V9L");
+/// assert!("".parse::<DeviceId>().is_err());
+/// ```
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct DeviceId(String);
+
+/// Prefix of identifiers based on the EDID, in place of the driver.
+const EDID_KIND: &str = "edid";
+
+impl DeviceId {
+    /// Returns the identifier of a device given its driver, its path, and the identity of its
+    /// display when it is external.
+    pub(crate) fn new(driver: &str, path: &str, external_display: Option<DisplayInfo>) -> Self {
+        let serial = external_display.and_then(|info| {
+            let serial = match (info.serial, info.serial_number) {
+                (Some(serial), _) => serial,
+                (None, Some(number)) => format!("{number:08X}"),
+                (None, None) => return None,
+            };
+            Some(format!(
+                "{}-{:04X}-{serial}",
+                info.manufacturer_id, info.product_code
+            ))
+        });
+        match serial {
+            Some(serial) => DeviceId(format!("{EDID_KIND}@{serial}")),
+            None => DeviceId(format!("{driver}@{path}")),
+        }
+    }
+}
+
+impl fmt::Display for DeviceId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl FromStr for DeviceId {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        match s.split_once('@') {
+            Some((driver, location)) if !driver.is_empty() && !location.is_empty() => {
+                Ok(DeviceId(s.into()))
+            }
+            _ => Err(Error::InvalidDeviceId(s.into())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn display(serial_number: Option<u32>, serial: Option<&str>) -> DisplayInfo {
+        DisplayInfo {
+            manufacturer_id: "DEL".into(),
+            product_code: 0xa0bc,
+            serial_number,
+            serial: serial.map(Into::into),
+            model: Some("DELL U2415".into()),
+            manufacture_year: Some(2016),
+            manufacture_week: Some(3),
+            model_year: None,
+        }
+    }
+
+    #[test]
+    fn displays_keep_their_id_whichever_driver_controls_them() {
+        let path = "/sys/devices/pci0000:00/0000:00:02.0/drm/DP-1";
+        let info = display(Some(0x1234), Some("7MT0167B0V9L"));
+        let ddcci = DeviceId::new("ddcci", path, Some(info.clone()));
+        let i2c = DeviceId::new("i2c", "/sys/devices/other", Some(info));
+        assert_eq!(ddcci, i2c);
+        assert_eq!(ddcci.to_string(), "edid@DEL-A0BC-7MT0167B0V9L");
+
+        let id = DeviceId::new("i2c", path, Some(display(Some(0x1234), None)));
+        assert_eq!(id.to_string(), "edid@DEL-A0BC-00001234");
+    }
+
+    #[test]
+    fn devices_without_serial_are_identified_by_driver_and_path() {
+        let path = "/sys/devices/pci0000:00/0000:00:02.0/drm/eDP-1";
+        let id = DeviceId::new("intel_backlight", path, Some(display(None, None)));
+        assert_eq!(id.to_string(), format!("intel_backlight@{path}"));
+        assert_eq!(id, id.to_string().parse().unwrap());
+        assert_ne!(id, DeviceId::new("acpi_video0", path, None));
+    }
+}
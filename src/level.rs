@@ -0,0 +1,202 @@
+// Copyright (C) 2022 The brightness project authors. Distributed under the 0BSD license.
+
+//! Fractional brightness levels.
+
+use crate::{Error, conversion::scale_ratio};
+use std::{cmp::Ordering, fmt};
+
+/// Denominator of levels created from a fraction, which represents every `f64` above 2^-10
+/// exactly.
+const FRACTION_DENOMINATOR: u64 = 1 << 63;
+
+/// Brightness level as a fraction of the maximum brightness, from 0.0 to 1.0.
+///
+/// Constructors reject values out of range instead of clamping them. Levels created from a
+/// percentage or a raw value are exact, so converting them back rounds like
+/// [`Brightness::set`](crate::blocking::Brightness::set) and
+/// [`Brightness::get`](crate::blocking::Brightness::get) do.
+///
+/// # Example
+///
+/// ```rust
+/// use brightness::Level;
+///
+/// let level = Level::from_percentage(25).unwrap();
+/// assert_eq!(level.fraction(), 0.25);
+/// assert_eq!(level.to_raw(96000), 24000);
+/// assert_eq!(Level::from_percentage(58).unwrap().to_raw(25), 15);
+/// assert_eq!(Level::from_raw(3, 7).unwrap().to_percentage(), 43);
+/// assert!(Level::new(1.5).is_err());
+/// ```
+#[derive(Clone, Copy)]
+pub struct Level {
+    numerator: u64,
+    denominator: u64,
+}
+
+impl Level {
+    /// Lowest brightness level.
+    pub const MIN: Level = Level {
+        numerator: 0,
+        denominator: 1,
+    };
+
+    /// Highest brightness level.
+    pub const MAX: Level = Level {
+        numerator: 1,
+        denominator: 1,
+    };
+
+    /// Returns the level for a fraction between 0.0 and 1.0.
+    pub fn new(fraction: f64) -> Result<Self, Error> {
+        if (0.0..=1.0).contains(&fraction) {
+            Ok(Self::from_fraction(fraction))
+        } else {
+            Err(Error::LevelOutOfRange(fraction))
+        }
+    }
+
+    /// Returns the level for a percentage between 0 and 100.
+    pub fn from_percentage(percentage: u32) -> Result<Self, Error> {
+        Self::from_raw(percentage, 100)
+    }
+
+    /// Returns the level for a raw value between 0 and `max`.
+    pub fn from_raw(raw: u32, max: u32) -> Result<Self, Error> {
+        match (raw, max) {
+            (0, _) => Ok(Self::MIN),
+            (_, 0) => Err(Error::LevelOutOfRange(f64::INFINITY)),
+            _ if raw > max => Err(Error::LevelOutOfRange(f64::from(raw) / f64::from(max))),
+            _ => Ok(Level {
+                numerator: raw.into(),
+                denominator: max.into(),
+            }),
+        }
+    }
+
+    /// Returns the level as a fraction between 0.0 and 1.0.
+    pub fn fraction(self) -> f64 {
+        self.numerator as f64 / self.denominator as f64
+    }
+
+    /// Returns the level as a percentage, rounded to the nearest integer, halves rounding up.
+    pub fn to_percentage(self) -> u32 {
+        scale_ratio(self.numerator, self.denominator, 100)
+    }
+
+    /// Returns the level as a raw value between 0 and `max`, rounded to the nearest integer,
+    /// halves rounding up.
+    pub fn to_raw(self, max: u32) -> u32 {
+        scale_ratio(self.numerator, self.denominator, max)
+    }
+
+    /// Returns the level for a fraction, clamped between 0.0 and 1.0.
+    pub(crate) fn from_fraction_clamped(fraction: f64) -> Self {
+        Self::from_fraction(fraction.clamp(0.0, 1.0))
+    }
+
+    /// Returns the level for a raw value read from a device, tolerating values above `max`.
+    pub(crate) fn from_raw_clamped(raw: u32, max: u32) -> Self {
+        Self::from_raw(raw.min(max), max).unwrap_or(Self::MIN)
+    }
+
+    /// Returns the level for a fraction between 0.0 and 1.0, NaN being 0.0.
+    fn from_fraction(fraction: f64) -> Self {
+        Level {
+            numerator: (fraction * FRACTION_DENOMINATOR as f64).round() as u64,
+            denominator: FRACTION_DENOMINATOR,
+        }
+    }
+}
+
+impl Default for Level {
+    fn default() -> Self {
+        Self::MIN
+    }
+}
+
+impl fmt::Debug for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Level").field(&self.fraction()).finish()
+    }
+}
+
+impl PartialEq for Level {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Level {}
+
+impl PartialOrd for Level {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Level {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let lhs = u128::from(self.numerator) * u128::from(other.denominator);
+        let rhs = u128::from(other.numerator) * u128::from(self.denominator);
+        lhs.cmp(&rhs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conversion::{percentage_to_raw, raw_to_percentage};
+
+    #[test]
+    fn conversions_match_integer_rounding() {
+        for max in 1..=2000 {
+            for percentage in 0..=100 {
+                let level = Level::from_percentage(percentage).unwrap();
+                assert_eq!(level.to_raw(max), percentage_to_raw(percentage, max));
+            }
+            for raw in 0..=max {
+                let level = Level::from_raw(raw, max).unwrap();
+                assert_eq!(level.to_percentage(), raw_to_percentage(raw, max));
+            }
+        }
+        // Cases that rounded differently when computed with floating point.
+        assert_eq!(Level::from_percentage(58).unwrap().to_raw(25), 15);
+        assert_eq!(Level::from_raw(23, 40).unwrap().to_percentage(), 58);
+    }
+
+    #[test]
+    fn equal_levels_compare_equal_whatever_their_origin() {
+        assert_eq!(
+            Level::from_percentage(50).unwrap(),
+            Level::new(0.5).unwrap()
+        );
+        assert_eq!(
+            Level::from_raw(2, 4).unwrap(),
+            Level::from_percentage(50).unwrap()
+        );
+        assert_eq!(Level::from_raw(7, 7).unwrap(), Level::MAX);
+        assert_eq!(Level::new(0.0).unwrap(), Level::MIN);
+        assert!(Level::from_raw(1, 3).unwrap() < Level::from_percentage(34).unwrap());
+        assert!(Level::from_raw(1, 3).unwrap() > Level::from_percentage(33).unwrap());
+    }
+
+    #[test]
+    fn fractions_round_trip() {
+        for fraction in [0.0, 0.001, 0.1, 0.25, 0.58, 1.0 / 3.0, 0.999_999, 1.0] {
+            let level = Level::new(fraction).unwrap();
+            assert_eq!(level.fraction(), fraction);
+        }
+        assert_eq!(Level::new(1.0).unwrap().to_raw(u32::MAX), u32::MAX);
+    }
+
+    #[test]
+    fn out_of_range_values_are_rejected() {
+        assert!(Level::new(-0.1).is_err());
+        assert!(Level::new(f64::NAN).is_err());
+        assert!(Level::from_percentage(101).is_err());
+        assert!(Level::from_raw(8, 7).is_err());
+        assert!(Level::from_raw(1, 0).is_err());
+        assert_eq!(Level::from_raw(0, 0).unwrap(), Level::MIN);
+    }
+}
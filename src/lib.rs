@@ -7,6 +7,13 @@
 //!
 //! This crate provides definitions to get and set display brightness.
 //!
+//! Monitors controlled through DDC/CI also expose settings like contrast, input source and power
+//! mode through the `MonitorControl` traits.
+//!
+//! Raw and platform backlights don't go below 5% by default, as some panels turn off at 0. The
+//! floor can be changed for each device with `set_min_level` or for all of them with
+//! `set_global_min_level`.
+//!
 //! Linux and Windows are supported.
 //!
 //! # Example
@@ -30,15 +37,27 @@
 //!
 //! # Linux
 //!
-//! This crate interacts with devices found at `/sys/class/backlight`. This means that the
+//! This crate interacts with devices found at `/sys/class/backlight`. External displays are controlled
+//! via DDC/CI, either through the
 //! [ddcci-backlight](https://gitlab.com/ddcci-driver-linux/ddcci-driver-linux#ddcci-backlight-monitor-backlight-driver)
-//! kernel driver is required to control external displays (via DDC/CI).
+//! kernel driver when it is loaded, or directly over `/dev/i2c-*` (which requires read and write
+//! permission on these files, e.g. by loading the `i2c-dev` module and joining the `i2c` group).
+//! Only the latter supports the `MonitorControl` traits, as the kernel driver holds the I2C address
+//! of the monitors it handles.
 //!
 //! Setting brightness is attempted using D-Bus and logind, which requires
 //! [systemd 243 or newer](https://github.com/systemd/systemd/blob/877aa0bdcc2900712b02dac90856f181b93c4e40/NEWS#L262).
-//! If this fails because the method is not available, the desired brightness is written to
-//! `/sys/class/backlight/$DEVICE/brightness`, which requires permission (`udev` rules can help with
-//! that).
+//! If this fails, the desired brightness is written to `/sys/class/backlight/$DEVICE/brightness`,
+//! which requires permission (`udev` rules can help with that). When logind lacks the method, it is
+//! not tried again; other failures only fall back for that change. If writing the file fails too, the
+//! error reports both failures.
+//!
+//! `linux::Context` enumerates devices under a different sysfs root, e.g. a fake backlight tree used
+//! for testing. It also selects the logind session to use (e.g. the active session of `seat0` for a
+//! system daemon) and whether logind or the `brightness` file is tried first.
+//!
+//! Keyboard backlights and other LEDs found at `/sys/class/leds` are also controlled when selected
+//! with `Context::set_device_classes`. LEDs that can only be switched on and off are left out.
 //!
 //! # Contribute
 //!
@@ -51,13 +70,31 @@
 use std::error::Error as StdError;
 use thiserror::Error;
 
+mod backlight;
+mod batch;
 pub mod blocking;
+mod capabilities;
+mod conversion;
+#[cfg(target_os = "linux")]
+mod ddc;
+mod edid;
+mod floor;
+mod hotplug;
+mod id;
+mod level;
+#[cfg(feature = "mock")]
+#[cfg_attr(docsrs, doc(cfg(feature = "mock")))]
+pub mod mock;
+mod perception;
+mod transition;
+mod vcp;
+mod watch;
 
 #[cfg(feature = "async")]
 #[cfg_attr(docsrs, doc(cfg(feature = "async")))]
 cfg_if::cfg_if! {
     if #[cfg(target_os = "linux")] {
-        mod linux;
+        pub mod linux;
         use self::linux as platform;
     } else if #[cfg(windows)] {
         pub mod windows;
@@ -70,9 +107,23 @@ cfg_if::cfg_if! {
 #[cfg(feature = "async")]
 #[cfg_attr(docsrs, doc(cfg(feature = "async")))]
 mod r#async {
-    use super::{Error, platform};
-    use futures::{Stream, StreamExt};
-    use std::future::Future;
+    use super::{
+        BatchReport, Capabilities, ColorPreset, DeviceId, DeviceType, DisplayInfo, Easing, Error,
+        InputSource, Level, Perception, PowerMode, Scale, VcpValue, backlight, batch,
+        conversion::{STEP, adjusted_raw},
+        floor, platform,
+        transition::Steps,
+        vcp,
+        watch::Watcher,
+    };
+    use async_io::Timer;
+    use blocking::unblock;
+    use futures::{Stream, StreamExt, TryStreamExt, future::join_all, stream};
+    use std::{
+        future::{self, Future},
+        pin::pin,
+        time::{Duration, Instant},
+    };
 
     /// Async interface to get and set brightness.
     pub trait Brightness {
@@ -82,36 +133,643 @@ mod r#async {
         /// Returns the current brightness as a percentage.
         fn get(&self) -> impl Future<Output = Result<u32, Error>> + Send;
 
-        /// Sets the brightness as a percentage.
+        /// Sets the brightness as a percentage. [`BrightnessDevice`] fails with
+        /// [`Error::LevelOutOfRange`] for percentages above 100.
         fn set(&mut self, percentage: u32) -> impl Future<Output = Result<(), Error>> + Send;
+
+        /// Returns the current brightness in device-specific units, between 0 and
+        /// [`max_raw`](Brightness::max_raw).
+        fn get_raw(&self) -> impl Future<Output = Result<u32, Error>> + Send;
+
+        /// Returns the maximum brightness in device-specific units.
+        fn max_raw(&self) -> impl Future<Output = Result<u32, Error>> + Send;
+
+        /// Sets the brightness in device-specific units. Values above
+        /// [`max_raw`](Brightness::max_raw) are clamped.
+        fn set_raw(&mut self, value: u32) -> impl Future<Output = Result<(), Error>> + Send;
+
+        /// Returns the current brightness level.
+        fn get_level(&self) -> impl Future<Output = Result<Level, Error>> + Send
+        where
+            Self: Sync,
+        {
+            async move {
+                let max = self.max_raw().await?;
+                let raw = self.get_raw().await?;
+                Ok(Level::from_raw_clamped(raw, max))
+            }
+        }
+
+        /// Sets the brightness level.
+        fn set_level(&mut self, level: Level) -> impl Future<Output = Result<(), Error>> + Send
+        where
+            Self: Send,
+        {
+            async move {
+                let max = self.max_raw().await?;
+                self.set_raw(level.to_raw(max)).await
+            }
+        }
+
+        /// Changes the brightness by `delta` percentage points.
+        ///
+        /// The new brightness is computed from the current raw value, which changes by at least
+        /// one step unless it is already at the end of the range.
+        fn adjust(&mut self, delta: i32) -> impl Future<Output = Result<(), Error>> + Send
+        where
+            Self: Send,
+        {
+            async move {
+                let max = self.max_raw().await?;
+                let raw = self.get_raw().await?;
+                let target = adjusted_raw(raw, max, delta, (Level::MIN, Level::MAX), None);
+                if target == raw {
+                    return Ok(());
+                }
+                self.set_raw(target).await
+            }
+        }
+
+        /// Increases the brightness by 5 percentage points, as for a brightness key.
+        fn step_up(&mut self) -> impl Future<Output = Result<(), Error>> + Send
+        where
+            Self: Send,
+        {
+            self.adjust(STEP)
+        }
+
+        /// Decreases the brightness by 5 percentage points, as for a brightness key.
+        fn step_down(&mut self) -> impl Future<Output = Result<(), Error>> + Send
+        where
+            Self: Send,
+        {
+            self.adjust(-STEP)
+        }
     }
 
     /// Async brightness device.
     #[derive(Debug)]
-    pub struct BrightnessDevice(pub(crate) platform::AsyncDeviceImpl);
+    pub struct BrightnessDevice {
+        pub(crate) inner: DeviceImpl,
+        perception: Perception,
+        adjust_bounds: (Level, Level),
+        min_level: Option<Level>,
+    }
+
+    #[derive(Debug)]
+    pub(crate) enum DeviceImpl {
+        Platform(platform::AsyncDeviceImpl),
+        #[cfg(feature = "mock")]
+        Mock(crate::mock::MockDevice),
+    }
+
+    impl BrightnessDevice {
+        pub(crate) fn new(inner: DeviceImpl) -> Self {
+            Self {
+                inner,
+                perception: Perception::default(),
+                adjust_bounds: (Level::MIN, Level::MAX),
+                min_level: None,
+            }
+        }
+
+        pub(crate) fn from_blocking(device: crate::blocking::BrightnessDevice) -> Self {
+            let (inner, perception, adjust_bounds, min_level) = device.into_parts();
+            let inner = match inner {
+                crate::blocking::DeviceImpl::Platform(d) => {
+                    DeviceImpl::Platform(platform::AsyncDeviceImpl::from_blocking(d))
+                }
+                #[cfg(feature = "mock")]
+                crate::blocking::DeviceImpl::Mock(d) => DeviceImpl::Mock(d),
+            };
+            Self {
+                inner,
+                perception,
+                adjust_bounds,
+                min_level,
+            }
+        }
+
+        /// Returns the mapping applied to percentages and levels.
+        pub fn perception(&self) -> Perception {
+            self.perception
+        }
+
+        /// Sets the mapping applied to percentages and levels. The default is
+        /// [`Perception::Linear`].
+        pub fn set_perception(&mut self, perception: Perception) {
+            self.perception = perception;
+        }
+
+        /// Returns the lowest and highest levels reached by [`adjust`](Brightness::adjust).
+        pub fn adjust_bounds(&self) -> (Level, Level) {
+            self.adjust_bounds
+        }
+
+        /// Sets the lowest and highest levels reached by [`adjust`](Brightness::adjust), on the
+        /// scale given by the [`perception`](Self::perception). The default is the whole range.
+        pub fn set_adjust_bounds(&mut self, min: Level, max: Level) {
+            self.adjust_bounds = if min <= max { (min, max) } else { (max, min) };
+        }
+
+        /// Returns the lowest level written to the device, as a fraction of
+        /// [`max_raw`](Brightness::max_raw) whatever the [`perception`](Self::perception).
+        ///
+        /// Lower values are raised to it by every function setting the brightness. It is the level
+        /// set with [`set_min_level`](Self::set_min_level) if any, otherwise the level set with
+        /// [`set_global_min_level`](crate::set_global_min_level) if any, otherwise 5% for devices
+        /// of type [`DeviceType::Raw`] and [`DeviceType::Platform`] and 0 for other devices.
+        pub fn min_level(&self) -> Level {
+            floor::min_level(self.min_level, self.device_type())
+        }
+
+        /// Sets the lowest level written to the device, or restores the default with `None`.
+        /// [`Level::MIN`] removes the floor.
+        pub fn set_min_level(&mut self, level: Option<Level>) {
+            self.min_level = level;
+        }
+
+        /// Returns the scale reported by the device.
+        pub fn scale(&self) -> Scale {
+            match &self.inner {
+                DeviceImpl::Platform(d) => d.scale(),
+                #[cfg(feature = "mock")]
+                DeviceImpl::Mock(d) => d.scale(),
+            }
+        }
+
+        /// Returns how the backlight is controlled.
+        pub fn device_type(&self) -> DeviceType {
+            match &self.inner {
+                DeviceImpl::Platform(d) => d.device_type(),
+                #[cfg(feature = "mock")]
+                DeviceImpl::Mock(d) => d.device_type(),
+            }
+        }
+
+        /// Returns the name of the connector of the display, e.g. `eDP-1` or `DP-2`, if known.
+        ///
+        /// On Linux, this is the DRM connector the device is matched with, as described in
+        /// [`display_info`](Self::display_info). On Windows, only the connector type is known,
+        /// e.g. `HDMI`.
+        pub fn connector_name(&self) -> Option<String> {
+            match &self.inner {
+                DeviceImpl::Platform(d) => d.connector_name(),
+                #[cfg(feature = "mock")]
+                DeviceImpl::Mock(d) => d.connector_name(),
+            }
+        }
+
+        /// Returns whether the device controls a built-in panel, e.g. of a laptop.
+        ///
+        /// On Linux, backlight devices whose connector is unknown are assumed to, except for
+        /// monitors controlled by the ddcci driver.
+        pub fn is_internal(&self) -> bool {
+            match &self.inner {
+                DeviceImpl::Platform(d) => d.is_internal(),
+                #[cfg(feature = "mock")]
+                DeviceImpl::Mock(d) => d.is_internal(),
+            }
+        }
+
+        /// Returns the identity of the display, decoded from its EDID, or `None` if the display
+        /// can't be identified.
+        ///
+        /// On Linux, the EDID is read from the DRM connector of the display. Backlight devices that
+        /// aren't registered under a connector are matched with the built-in panel of their
+        /// graphics card, if it has only one. On Windows, the EDID is read from the registry.
+        pub fn display_info(&self) -> Result<Option<DisplayInfo>, Error> {
+            let edid = match &self.inner {
+                DeviceImpl::Platform(d) => d.edid()?,
+                #[cfg(feature = "mock")]
+                DeviceImpl::Mock(d) => d.edid()?,
+            };
+            edid.map(|edid| DisplayInfo::from_edid(&edid)).transpose()
+        }
+
+        /// Returns an identifier of the device that stays the same across reboots, see
+        /// [`DeviceId`].
+        pub fn id(&self) -> DeviceId {
+            let (driver, path) = match &self.inner {
+                DeviceImpl::Platform(d) => (d.driver(), d.stable_path()),
+                #[cfg(feature = "mock")]
+                DeviceImpl::Mock(d) => (d.driver(), d.stable_path()),
+            };
+            // Displays with an invalid EDID are identified by path like unknown displays.
+            let display = (!self.is_internal())
+                .then(|| self.display_info().ok().flatten())
+                .flatten();
+            DeviceId::new(&driver, &path, display)
+        }
+
+        /// Returns a stream of brightness changes.
+        ///
+        /// The stream first yields the current brightness as a percentage, then the new brightness
+        /// each time it changes. It never ends, errors included. On Linux, changes are notified by
+        /// sysfs; changes that aren't notified and other platforms are noticed by polling every
+        /// second. Monitors controlled over I2C on Linux can't be watched, as they are too slow to
+        /// poll; for them, a single error is yielded instead.
+        pub fn watch(&self) -> impl Stream<Item = Result<u32, Error>> + Send + '_ {
+            let watcher = match self.watcher() {
+                Ok(w) => w,
+                Err(e) => return stream::once(future::ready(Err(e))).left_stream(),
+            };
+            stream::unfold((watcher, None), move |(mut watcher, last)| async move {
+                loop {
+                    if last.is_some() {
+                        let (w, result) = unblock(move || {
+                            let result = watcher.wait();
+                            (watcher, result)
+                        })
+                        .await;
+                        watcher = w;
+                        if let Err(e) = result {
+                            return Some((Err(e), (watcher, last)));
+                        }
+                    }
+                    match self.get().await {
+                        Ok(value) if last == Some(value) => {}
+                        Ok(value) => return Some((Ok(value), (watcher, Some(value)))),
+                        Err(e) => return Some((Err(e), (watcher, last))),
+                    }
+                }
+            })
+            .right_stream()
+        }
+
+        /// Gradually changes the brightness to `target` over `duration`, ending exactly at
+        /// `target`.
+        ///
+        /// Raw values are written at most every 10 milliseconds. Dropping the future cancels the
+        /// transition.
+        pub async fn transition_to(
+            &mut self,
+            target: Level,
+            duration: Duration,
+            easing: Easing,
+        ) -> Result<(), Error> {
+            let max = self.max_raw().await?;
+            let current = self.get_level().await?;
+            let mut steps = Steps::new(current, target, max, self.correction(), easing, duration);
+            let start = Instant::now();
+            while let Some((offset, raw)) = steps.next() {
+                Timer::at(start + offset).await;
+                self.set_raw(raw).await?;
+            }
+            Ok(())
+        }
+
+        fn watcher(&self) -> Result<Watcher, Error> {
+            Ok(match &self.inner {
+                DeviceImpl::Platform(d) => d.watcher()?,
+                #[cfg(feature = "mock")]
+                DeviceImpl::Mock(d) => d.watcher()?,
+            })
+        }
+
+        fn parent(&self) -> Option<backlight::Parent> {
+            match &self.inner {
+                DeviceImpl::Platform(d) => d.parent(),
+                #[cfg(feature = "mock")]
+                DeviceImpl::Mock(_) => None,
+            }
+        }
+
+        fn correction(&self) -> Option<Perception> {
+            match self.perception {
+                Perception::Linear => None,
+                p => (self.scale() != Scale::NonLinear).then_some(p),
+            }
+        }
+    }
 
     impl Brightness for BrightnessDevice {
         async fn device_name(&self) -> Result<String, Error> {
-            self.0.device_name().await
+            match &self.inner {
+                DeviceImpl::Platform(d) => d.device_name().await,
+                #[cfg(feature = "mock")]
+                DeviceImpl::Mock(d) => crate::blocking::Brightness::device_name(d),
+            }
         }
 
         async fn get(&self) -> Result<u32, Error> {
-            self.0.get().await
+            if self.correction().is_some() {
+                return Ok(self.get_level().await?.to_percentage());
+            }
+            match &self.inner {
+                DeviceImpl::Platform(d) => d.get().await,
+                #[cfg(feature = "mock")]
+                DeviceImpl::Mock(d) => crate::blocking::Brightness::get(d),
+            }
         }
 
         async fn set(&mut self, percentage: u32) -> Result<(), Error> {
-            self.0.set(percentage).await
+            let level = Level::from_percentage(percentage)?;
+            if self.correction().is_some() || self.min_level() > Level::MIN {
+                return self.set_level(level).await;
+            }
+            match &mut self.inner {
+                DeviceImpl::Platform(d) => d.set(percentage).await,
+                #[cfg(feature = "mock")]
+                DeviceImpl::Mock(d) => crate::blocking::Brightness::set(d, percentage),
+            }
+        }
+
+        async fn get_raw(&self) -> Result<u32, Error> {
+            match &self.inner {
+                DeviceImpl::Platform(d) => d.get_raw().await,
+                #[cfg(feature = "mock")]
+                DeviceImpl::Mock(d) => crate::blocking::Brightness::get_raw(d),
+            }
+        }
+
+        async fn max_raw(&self) -> Result<u32, Error> {
+            match &self.inner {
+                DeviceImpl::Platform(d) => d.max_raw().await,
+                #[cfg(feature = "mock")]
+                DeviceImpl::Mock(d) => crate::blocking::Brightness::max_raw(d),
+            }
+        }
+
+        async fn set_raw(&mut self, value: u32) -> Result<(), Error> {
+            let value = match self.min_level() {
+                min if min == Level::MIN => value,
+                min => value.max(floor::min_raw(min, self.max_raw().await?)),
+            };
+            match &mut self.inner {
+                DeviceImpl::Platform(d) => d.set_raw(value).await,
+                #[cfg(feature = "mock")]
+                DeviceImpl::Mock(d) => crate::blocking::Brightness::set_raw(d, value),
+            }
+        }
+
+        async fn get_level(&self) -> Result<Level, Error> {
+            let max = self.max_raw().await?;
+            let level = Level::from_raw_clamped(self.get_raw().await?, max);
+            Ok(match self.correction() {
+                Some(perception) => perception.perceive(level),
+                None => level,
+            })
+        }
+
+        async fn set_level(&mut self, level: Level) -> Result<(), Error> {
+            let level = match self.correction() {
+                Some(perception) => perception.linearize(level),
+                None => level,
+            };
+            let max = self.max_raw().await?;
+            self.set_raw(level.to_raw(max)).await
+        }
+
+        async fn adjust(&mut self, delta: i32) -> Result<(), Error> {
+            let max = self.max_raw().await?;
+            let raw = self.get_raw().await?;
+            // Values already below the floor aren't raised by stepping down.
+            let min = floor::min_raw(self.min_level(), max).min(raw);
+            let target =
+                adjusted_raw(raw, max, delta, self.adjust_bounds, self.correction()).max(min);
+            if target == raw {
+                return Ok(());
+            }
+            self.set_raw(target).await
+        }
+    }
+
+    /// Async interface to monitor settings exposed as VCP features through DDC/CI.
+    ///
+    /// Only monitors controlled through DDC/CI support these settings; other devices fail with
+    /// [`Error::GettingDeviceInfo`]. Monitors commonly implement a subset of the features.
+    ///
+    /// On Linux, monitors handled by the ddcci kernel driver are backlight devices like any other,
+    /// and don't support these settings either, as the driver holds their I2C address. Unloading
+    /// the driver makes them available over `/dev/i2c-*` instead.
+    pub trait MonitorControl {
+        /// Returns the value of a VCP feature.
+        fn get_vcp(&self, code: u8) -> impl Future<Output = Result<VcpValue, Error>> + Send;
+
+        /// Sets the value of a VCP feature.
+        fn set_vcp(
+            &mut self,
+            code: u8,
+            value: u16,
+        ) -> impl Future<Output = Result<(), Error>> + Send;
+
+        /// Returns the MCCS capabilities string. Reading it can take seconds.
+        fn capabilities_string(&self) -> impl Future<Output = Result<String, Error>> + Send;
+
+        /// Returns the parsed MCCS capabilities, e.g. to know which VCP features are supported.
+        fn capabilities(&self) -> impl Future<Output = Result<Capabilities, Error>> + Send
+        where
+            Self: Sync,
+        {
+            async move { self.capabilities_string().await?.parse() }
+        }
+
+        /// Returns the contrast.
+        fn contrast(&self) -> impl Future<Output = Result<VcpValue, Error>> + Send {
+            self.get_vcp(vcp::CONTRAST)
+        }
+
+        /// Sets the contrast, between 0 and its maximum.
+        fn set_contrast(&mut self, value: u16) -> impl Future<Output = Result<(), Error>> + Send {
+            self.set_vcp(vcp::CONTRAST, value)
+        }
+
+        /// Returns the audio speaker volume.
+        fn volume(&self) -> impl Future<Output = Result<VcpValue, Error>> + Send {
+            self.get_vcp(vcp::VOLUME)
+        }
+
+        /// Sets the audio speaker volume, between 0 and its maximum.
+        fn set_volume(&mut self, value: u16) -> impl Future<Output = Result<(), Error>> + Send {
+            self.set_vcp(vcp::VOLUME, value)
+        }
+
+        /// Returns the selected video input.
+        fn input_source(&self) -> impl Future<Output = Result<InputSource, Error>> + Send
+        where
+            Self: Sync,
+        {
+            async move {
+                let value = self.get_vcp(vcp::INPUT_SOURCE).await?;
+                Ok(InputSource::from_value(value.selected()))
+            }
+        }
+
+        /// Selects the video input.
+        fn set_input_source(
+            &mut self,
+            input: InputSource,
+        ) -> impl Future<Output = Result<(), Error>> + Send {
+            self.set_vcp(vcp::INPUT_SOURCE, input.value().into())
+        }
+
+        /// Returns the power mode.
+        fn power_mode(&self) -> impl Future<Output = Result<PowerMode, Error>> + Send
+        where
+            Self: Sync,
+        {
+            async move {
+                let value = self.get_vcp(vcp::POWER_MODE).await?;
+                Ok(PowerMode::from_value(value.selected()))
+            }
+        }
+
+        /// Sets the power mode. Monitors may stop answering until turned back on.
+        fn set_power_mode(
+            &mut self,
+            mode: PowerMode,
+        ) -> impl Future<Output = Result<(), Error>> + Send {
+            self.set_vcp(vcp::POWER_MODE, mode.value().into())
+        }
+
+        /// Returns the color preset.
+        fn color_preset(&self) -> impl Future<Output = Result<ColorPreset, Error>> + Send
+        where
+            Self: Sync,
+        {
+            async move {
+                let value = self.get_vcp(vcp::COLOR_PRESET).await?;
+                Ok(ColorPreset::from_value(value.selected()))
+            }
+        }
+
+        /// Selects a color preset.
+        fn set_color_preset(
+            &mut self,
+            preset: ColorPreset,
+        ) -> impl Future<Output = Result<(), Error>> + Send {
+            self.set_vcp(vcp::COLOR_PRESET, preset.value().into())
+        }
+    }
+
+    impl MonitorControl for BrightnessDevice {
+        async fn get_vcp(&self, code: u8) -> Result<VcpValue, Error> {
+            match &self.inner {
+                DeviceImpl::Platform(d) => Ok(d.get_vcp(code).await?),
+                #[cfg(feature = "mock")]
+                DeviceImpl::Mock(d) => Ok(d.get_vcp(code)?),
+            }
+        }
+
+        async fn set_vcp(&mut self, code: u8, value: u16) -> Result<(), Error> {
+            match &self.inner {
+                DeviceImpl::Platform(d) => Ok(d.set_vcp(code, value).await?),
+                #[cfg(feature = "mock")]
+                DeviceImpl::Mock(d) => Ok(d.set_vcp(code, value)?),
+            }
+        }
+
+        async fn capabilities_string(&self) -> Result<String, Error> {
+            match &self.inner {
+                DeviceImpl::Platform(d) => Ok(d.capabilities_string().await?),
+                #[cfg(feature = "mock")]
+                DeviceImpl::Mock(d) => Ok(d.capabilities_string()?),
+            }
         }
     }
 
     /// Returns all brightness devices on the running system.
     pub fn brightness_devices() -> impl Stream<Item = Result<BrightnessDevice, Error>> {
-        platform::brightness_devices().map(|r| r.map(BrightnessDevice).map_err(Into::into))
+        platform::brightness_devices().map(|r| {
+            r.map(|d| BrightnessDevice::new(DeviceImpl::Platform(d)))
+                .map_err(Into::into)
+        })
+    }
+
+    /// Returns the device with the given identifier on the running system, if it is present.
+    pub async fn brightness_device_by_id(id: &DeviceId) -> Result<Option<BrightnessDevice>, Error> {
+        let devices = brightness_devices().try_filter(|d| future::ready(d.id() == *id));
+        pin!(devices).try_next().await
+    }
+
+    /// Sets the brightness of all devices on the running system as a percentage.
+    ///
+    /// Devices are set concurrently, so that a slow monitor doesn't delay the others. Failures are
+    /// reported per device instead of stopping the operation.
+    pub async fn set_all(percentage: u32) -> BatchReport<BrightnessDevice, ()> {
+        set_all_of(brightness_devices(), percentage).await
+    }
+
+    /// Sets the brightness of the given devices, like [`set_all`].
+    pub(crate) async fn set_all_of(
+        devices: impl Stream<Item = Result<BrightnessDevice, Error>>,
+        percentage: u32,
+    ) -> BatchReport<BrightnessDevice, ()> {
+        let (mut devices, listing_errors) = batch::partition(devices.collect::<Vec<_>>().await);
+        let results = join_all(devices.iter_mut().map(|d| d.set(percentage))).await;
+        BatchReport {
+            results: devices.into_iter().zip(results).collect(),
+            listing_errors,
+        }
+    }
+
+    /// Returns the brightness of all devices on the running system as a percentage.
+    ///
+    /// Devices are read concurrently like in [`set_all`].
+    pub async fn get_all() -> BatchReport<BrightnessDevice, u32> {
+        get_all_of(brightness_devices()).await
+    }
+
+    /// Returns the brightness of the given devices, like [`get_all`].
+    pub(crate) async fn get_all_of(
+        devices: impl Stream<Item = Result<BrightnessDevice, Error>>,
+    ) -> BatchReport<BrightnessDevice, u32> {
+        let (devices, listing_errors) = batch::partition(devices.collect::<Vec<_>>().await);
+        let results = join_all(devices.iter().map(|d| d.get())).await;
+        BatchReport {
+            results: devices.into_iter().zip(results).collect(),
+            listing_errors,
+        }
+    }
+
+    /// Returns a stream of device events on the running system, starting with an
+    /// [`Added`](crate::DeviceEvent::Added) event for each existing device.
+    ///
+    /// On Linux, devices are tracked using kernel uevents. Changes that aren't notified and other
+    /// platforms are noticed by checking every second.
+    pub fn device_events() -> impl Stream<Item = Result<crate::DeviceEvent<BrightnessDevice>, Error>>
+    {
+        cfg_if::cfg_if! {
+            if #[cfg(target_os = "linux")] {
+                platform::Context::new().device_events()
+            } else {
+                crate::hotplug::events(crate::hotplug::Source::System)
+            }
+        }
+    }
+
+    /// Sorts devices by [`DeviceType`] preference and removes raw devices controlling the same
+    /// panel as a firmware or platform device, like systemd-backlight does.
+    pub fn dedup_backlights(devices: Vec<BrightnessDevice>) -> Vec<BrightnessDevice> {
+        backlight::dedup(devices, |d| (d.device_type(), d.parent()))
+    }
+
+    /// Returns the preferred brightness device on the running system, if any.
+    ///
+    /// See [`dedup_backlights`] for how devices are ranked.
+    pub async fn primary_backlight() -> Result<Option<BrightnessDevice>, Error> {
+        let devices = brightness_devices().try_collect().await?;
+        Ok(dedup_backlights(devices).into_iter().next())
     }
 }
 
 #[cfg(feature = "async")]
-pub use r#async::{Brightness, BrightnessDevice, brightness_devices};
+pub use r#async::{
+    Brightness, BrightnessDevice, MonitorControl, brightness_device_by_id, brightness_devices,
+    dedup_backlights, device_events, get_all, primary_backlight, set_all,
+};
+pub use backlight::DeviceType;
+pub use batch::BatchReport;
+pub use capabilities::{Capabilities, MccsVersion};
+pub use edid::DisplayInfo;
+pub use floor::{global_min_level, set_global_min_level};
+pub use hotplug::DeviceEvent;
+pub use id::DeviceId;
+pub use level::Level;
+pub use perception::{Perception, Scale};
+pub use transition::Easing;
+pub use vcp::{ColorPreset, InputSource, PowerMode, VcpValue};
 
 /// Errors used in this API
 #[derive(Debug, Error)]
@@ -138,4 +796,177 @@ pub enum Error {
         /// Cause
         source: Box<dyn StdError + Send + Sync>,
     },
+
+    /// Setting a monitor VCP feature failed
+    #[error("Setting VCP feature {code:#04x} failed for device {device}")]
+    SettingVcpFeature {
+        /// Device name
+        device: String,
+        /// VCP code of the feature
+        code: u8,
+        /// Cause
+        source: Box<dyn StdError + Send + Sync>,
+    },
+
+    /// A brightness level was not between 0.0 and 1.0
+    #[error("Brightness level {0} is out of range")]
+    LevelOutOfRange(f64),
+
+    /// A monitor capabilities string could not be parsed
+    #[error("Invalid monitor capabilities string: {0}")]
+    InvalidCapabilities(String),
+
+    /// A display EDID could not be decoded
+    #[error("Invalid display EDID: {0}")]
+    InvalidEdid(String),
+
+    /// A device identifier could not be parsed
+    #[error("Invalid device identifier {0:?}")]
+    InvalidDeviceId(String),
+}
+
+#[cfg(all(test, feature = "async", feature = "mock"))]
+mod tests {
+    use super::*;
+    use crate::mock::MockBackend;
+    use async_io::Timer;
+    use futures::{StreamExt, executor::block_on, future};
+    use std::{pin::pin, time::Duration};
+
+    fn device(backend: &MockBackend) -> BrightnessDevice {
+        block_on(backend.brightness_devices().next())
+            .unwrap()
+            .unwrap()
+    }
+
+    #[test]
+    fn transitions_end_exactly_at_the_target() {
+        let targets = [
+            (1000, Level::from_percentage(73).unwrap(), 730),
+            (1000, Level::new(1.0 / 3.0).unwrap(), 333),
+            (7, Level::from_percentage(50).unwrap(), 4),
+            (7, Level::MIN, 0),
+        ];
+        for easing in [Easing::Linear, Easing::EaseInOut] {
+            for (max, target, raw) in targets {
+                let backend = MockBackend::new().with_device("panel", max);
+                backend.set_value("panel", max / 2 + 1);
+                let mut device = device(&backend);
+                block_on(device.transition_to(target, Duration::from_millis(50), easing)).unwrap();
+                assert_eq!(backend.value("panel"), Some(raw), "max {max}, {easing:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn dropping_a_transition_stops_it() {
+        let backend = MockBackend::new().with_device("panel", 1000);
+        backend.set_value("panel", 0);
+        let mut device = device(&backend);
+        block_on(async {
+            // Steps are 60 ms apart, so the transition is far from done after the first one.
+            let mut transition =
+                pin!(device.transition_to(Level::MAX, Duration::from_secs(60), Easing::Linear));
+            while backend.value("panel") == Some(0) {
+                let poll = Timer::after(Duration::from_millis(1));
+                if let future::Either::Left(_) = future::select(transition.as_mut(), poll).await {
+                    panic!("the transition ended early");
+                }
+            }
+        });
+        let stopped_at = backend.value("panel").unwrap();
+        assert!(0 < stopped_at && stopped_at < 1000, "{stopped_at}");
+        block_on(Timer::after(Duration::from_millis(200)));
+        assert_eq!(backend.value("panel"), Some(stopped_at));
+    }
+
+    const FULL_RANGE: (Level, Level) = (Level::MIN, Level::MAX);
+
+    /// Returns the raw values reached by adjusting a device from `start` by each delta in turn,
+    /// checking that the blocking and async interfaces agree.
+    fn adjusted(max: u32, start: u32, bounds: (Level, Level), deltas: &[i32]) -> Vec<u32> {
+        let backend = MockBackend::new().with_device("panel", max);
+        backend.set_value("panel", start);
+        let mut blocking_device = backend
+            .blocking_brightness_devices()
+            .next()
+            .unwrap()
+            .unwrap();
+        blocking_device.set_adjust_bounds(bounds.0, bounds.1);
+        let blocking_values = deltas
+            .iter()
+            .map(|&delta| {
+                blocking::Brightness::adjust(&blocking_device, delta).unwrap();
+                backend.value("panel").unwrap()
+            })
+            .collect::<Vec<_>>();
+
+        backend.set_value("panel", start);
+        let mut device = device(&backend);
+        device.set_adjust_bounds(bounds.0, bounds.1);
+        let async_values = deltas
+            .iter()
+            .map(|&delta| {
+                block_on(device.adjust(delta)).unwrap();
+                backend.value("panel").unwrap()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(blocking_values, async_values);
+        blocking_values
+    }
+
+    #[test]
+    fn adjusting_moves_at_least_one_raw_step() {
+        assert_eq!(adjusted(3, 0, FULL_RANGE, &[1, 1, 1, 1]), [1, 2, 3, 3]);
+        assert_eq!(adjusted(3, 3, FULL_RANGE, &[-1, -1, -1, -1]), [2, 1, 0, 0]);
+        assert_eq!(
+            adjusted(3, 1, FULL_RANGE, &[0, 50, 100, -100]),
+            [1, 2, 3, 0]
+        );
+    }
+
+    #[test]
+    fn negative_deltas_lower_brightness() {
+        assert_eq!(adjusted(100, 50, FULL_RANGE, &[-10, -25, -5]), [40, 15, 10]);
+        assert_eq!(adjusted(255, 255, FULL_RANGE, &[-50, -60]), [128, 0]);
+    }
+
+    #[test]
+    fn adjusting_stays_within_bounds() {
+        let bounds = (
+            Level::from_percentage(30).unwrap(),
+            Level::from_percentage(60).unwrap(),
+        );
+        assert_eq!(
+            adjusted(100, 50, bounds, &[20, 5, -50, -5]),
+            [60, 60, 30, 30]
+        );
+        // Values already past the bounds don't move further away.
+        assert_eq!(adjusted(100, 80, bounds, &[5, -5]), [80, 75]);
+        assert_eq!(adjusted(100, 10, bounds, &[-5, 5]), [10, 15]);
+    }
+
+    #[test]
+    fn brightness_keys_step_by_five_points() {
+        let backend = MockBackend::new().with_device("panel", 3);
+        backend.set_value("panel", 1);
+        let blocking_device = backend
+            .blocking_brightness_devices()
+            .next()
+            .unwrap()
+            .unwrap();
+        blocking::Brightness::step_up(&blocking_device).unwrap();
+        assert_eq!(backend.value("panel"), Some(2));
+        blocking::Brightness::step_down(&blocking_device).unwrap();
+        assert_eq!(backend.value("panel"), Some(1));
+
+        let backend = MockBackend::new().with_device("panel", 200);
+        backend.set_value("panel", 100);
+        let mut device = device(&backend);
+        block_on(device.step_up()).unwrap();
+        assert_eq!(backend.value("panel"), Some(110));
+        block_on(device.step_down()).unwrap();
+        block_on(device.step_down()).unwrap();
+        assert_eq!(backend.value("panel"), Some(90));
+    }
 }
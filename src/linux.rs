@@ -2,97 +2,558 @@
 
 //! Platform-specific implementation for Linux.
 
+pub use crate::blocking::linux::{
+    BrightnessExt, Context, DeviceClass, LogindSession, PowerState, WriteMethod, WriteStrategy,
+};
+
 use crate::{
-    Error,
-    blocking::linux::{
-        BACKLIGHT_DIR, SESSION_INTERFACE, SESSION_OBJECT_PATH, SET_BRIGHTNESS_METHOD, SysError,
-        USER_DBUS_NAME, Value, read_value,
+    BrightnessDevice, DeviceType, Error, Scale, VcpValue,
+    r#async::DeviceImpl,
+    backlight::Parent,
+    blocking::{
+        Brightness,
+        linux::{
+            BlockingDeviceImpl, DdcDevice, MANAGER_INTERFACE, MANAGER_OBJECT_PATH,
+            PROPERTIES_INTERFACE, SEAT_INTERFACE, SESSION_INTERFACE, SET_BRIGHTNESS_METHOD,
+            SysError, SysfsDevice, USER_DBUS_NAME, Value, active_session_path, auto_session_path,
+            cached_system_bus, driver, forget_system_bus, read_value, share_system_bus,
+        },
     },
+    conversion::percentage_to_raw,
+    watch::Watcher,
 };
 use blocking::unblock;
-use futures::{Stream, StreamExt, future::ready};
+use futures::{Stream, StreamExt, stream};
+use std::sync::Arc;
+use zbus::zvariant::OwnedObjectPath;
 
 #[derive(Debug)]
-pub(crate) struct AsyncDeviceImpl {
-    device: String,
+pub(crate) enum AsyncDeviceImpl {
+    Sysfs(SysfsDevice),
+    Ddc(Arc<DdcDevice>),
+}
+
+impl Context {
+    /// Returns a stream of device events in this context, starting with an
+    /// [`Added`](crate::DeviceEvent::Added) event for each existing device.
+    ///
+    /// Devices of the real sysfs are tracked using kernel uevents, and devices of other sysfs
+    /// roots using inotify. Changes that aren't notified are noticed by checking every second.
+    pub fn device_events(
+        &self,
+    ) -> impl Stream<Item = Result<crate::DeviceEvent<BrightnessDevice>, Error>> + use<> {
+        crate::hotplug::events(crate::hotplug::Source::Sysfs(self.clone()))
+    }
+
+    /// Returns all brightness devices found in this context.
+    pub fn brightness_devices(
+        &self,
+    ) -> impl Stream<Item = Result<BrightnessDevice, Error>> + use<> {
+        devices(self).map(|r| {
+            r.map(|d| BrightnessDevice::new(DeviceImpl::Platform(d)))
+                .map_err(Into::into)
+        })
+    }
 }
 
 impl crate::Brightness for AsyncDeviceImpl {
     async fn device_name(&self) -> Result<String, Error> {
-        Ok(self.device.clone())
+        match self {
+            AsyncDeviceImpl::Sysfs(d) => d.device_name(),
+            AsyncDeviceImpl::Ddc(d) => d.device_name(),
+        }
     }
 
     async fn get(&self) -> Result<u32, Error> {
-        let max = read_value(&self.device, Value::Max)?;
-        let actual = read_value(&self.device, Value::Actual)?;
-        let percentage = if max == 0 {
-            0
-        } else {
-            (actual * 100 / max) as u32
-        };
-        Ok(percentage)
+        match self {
+            AsyncDeviceImpl::Sysfs(d) => d.get(),
+            AsyncDeviceImpl::Ddc(d) => {
+                let d = Arc::clone(d);
+                unblock(move || d.get()).await
+            }
+        }
     }
 
     async fn set(&mut self, percentage: u32) -> Result<(), Error> {
-        let percentage = percentage.min(100);
-        let max = read_value(&self.device, Value::Max)?;
-        let desired_value = (u64::from(percentage) * u64::from(max) / 100) as u32;
-        let desired = ("backlight", &self.device, desired_value);
-        let bus = zbus::Connection::system()
-            .await
-            .map_err(|e| Error::SettingBrightness {
-                device: self.device.clone(),
-                source: e.into(),
-            })?;
-        let response = bus
-            .call_method(
-                Some(USER_DBUS_NAME),
-                SESSION_OBJECT_PATH,
-                Some(SESSION_INTERFACE),
-                SET_BRIGHTNESS_METHOD,
-                &desired,
-            )
-            .await;
-        match response {
-            Ok(_) => Ok(()),
-            Err(zbus::Error::MethodError(..)) => {
-                // Setting brightness through dbus may not work on older systems that don't have
-                // the `SetBrightness` method. Fall back to writing to the brightness file (which
-                // requires permission).
-                set_value(self.device.clone(), desired_value).await?;
+        match self {
+            AsyncDeviceImpl::Sysfs(d) => {
+                let max = read_value(&d.context, d.class, &d.device, Value::Max)?;
+                write_raw(d, percentage_to_raw(percentage, max)).await
+            }
+            AsyncDeviceImpl::Ddc(d) => {
+                let d = Arc::clone(d);
+                unblock(move || d.set(percentage)).await
+            }
+        }
+    }
+
+    async fn get_raw(&self) -> Result<u32, Error> {
+        match self {
+            AsyncDeviceImpl::Sysfs(d) => d.get_raw(),
+            AsyncDeviceImpl::Ddc(d) => {
+                let d = Arc::clone(d);
+                unblock(move || d.get_raw()).await
+            }
+        }
+    }
+
+    async fn max_raw(&self) -> Result<u32, Error> {
+        match self {
+            AsyncDeviceImpl::Sysfs(d) => d.max_raw(),
+            AsyncDeviceImpl::Ddc(d) => {
+                let d = Arc::clone(d);
+                unblock(move || d.max_raw()).await
+            }
+        }
+    }
+
+    async fn set_raw(&mut self, value: u32) -> Result<(), Error> {
+        match self {
+            AsyncDeviceImpl::Sysfs(d) => {
+                let max = read_value(&d.context, d.class, &d.device, Value::Max)?;
+                write_raw(d, value.min(max)).await
+            }
+            AsyncDeviceImpl::Ddc(d) => {
+                let d = Arc::clone(d);
+                unblock(move || d.set_raw(value)).await
+            }
+        }
+    }
+}
+
+impl AsyncDeviceImpl {
+    pub(crate) fn from_blocking(device: BlockingDeviceImpl) -> Self {
+        match device {
+            BlockingDeviceImpl::Sysfs(d) => AsyncDeviceImpl::Sysfs(d),
+            BlockingDeviceImpl::Ddc(d) => AsyncDeviceImpl::Ddc(Arc::from(d)),
+        }
+    }
+
+    pub(crate) fn scale(&self) -> Scale {
+        match self {
+            AsyncDeviceImpl::Sysfs(d) => d.scale(),
+            AsyncDeviceImpl::Ddc(_) => Scale::Unknown,
+        }
+    }
+
+    pub(crate) fn device_type(&self) -> DeviceType {
+        match self {
+            AsyncDeviceImpl::Sysfs(d) => d.device_type(),
+            AsyncDeviceImpl::Ddc(_) => DeviceType::Unknown,
+        }
+    }
+
+    pub(crate) fn parent(&self) -> Option<Parent> {
+        match self {
+            AsyncDeviceImpl::Sysfs(d) => d.parent(),
+            AsyncDeviceImpl::Ddc(_) => None,
+        }
+    }
+
+    pub(crate) fn watcher(&self) -> Result<Watcher, SysError> {
+        match self {
+            AsyncDeviceImpl::Sysfs(d) => d.watcher(),
+            AsyncDeviceImpl::Ddc(d) => Err(d.watching_unsupported()),
+        }
+    }
+
+    pub(crate) async fn get_vcp(&self, code: u8) -> Result<VcpValue, SysError> {
+        match self {
+            AsyncDeviceImpl::Sysfs(d) => Err(d.ddc_unsupported()),
+            AsyncDeviceImpl::Ddc(d) => {
+                let d = Arc::clone(d);
+                unblock(move || d.get_vcp(code)).await
+            }
+        }
+    }
+
+    pub(crate) async fn set_vcp(&self, code: u8, value: u16) -> Result<(), SysError> {
+        match self {
+            AsyncDeviceImpl::Sysfs(d) => Err(d.ddc_unsupported()),
+            AsyncDeviceImpl::Ddc(d) => {
+                let d = Arc::clone(d);
+                unblock(move || d.set_vcp(code, value)).await
+            }
+        }
+    }
+
+    pub(crate) fn connector_name(&self) -> Option<String> {
+        match self {
+            AsyncDeviceImpl::Sysfs(d) => d.connector().map(|c| c.kind()),
+            AsyncDeviceImpl::Ddc(d) => Some(d.connector.kind()),
+        }
+    }
+
+    pub(crate) fn is_internal(&self) -> bool {
+        match self {
+            AsyncDeviceImpl::Sysfs(d) => d.is_internal(),
+            AsyncDeviceImpl::Ddc(_) => false,
+        }
+    }
+
+    pub(crate) fn edid(&self) -> Result<Option<Vec<u8>>, SysError> {
+        Ok(match self {
+            AsyncDeviceImpl::Sysfs(d) => d.edid(),
+            AsyncDeviceImpl::Ddc(d) => d.edid(),
+        })
+    }
+
+    pub(crate) fn power_state(&self) -> Result<PowerState, SysError> {
+        match self {
+            AsyncDeviceImpl::Sysfs(d) => d.power_state(),
+            AsyncDeviceImpl::Ddc(d) => Err(SysError::PowerUnsupported { device: d.name() }),
+        }
+    }
+
+    pub(crate) fn set_power(&self, state: PowerState) -> Result<(), Error> {
+        match self {
+            AsyncDeviceImpl::Sysfs(d) => d.set_power(state),
+            AsyncDeviceImpl::Ddc(d) => Err(SysError::PowerUnsupported { device: d.name() }.into()),
+        }
+    }
+
+    pub(crate) fn driver(&self) -> String {
+        match self {
+            AsyncDeviceImpl::Sysfs(d) => driver(&d.device),
+            AsyncDeviceImpl::Ddc(d) => driver(&d.name()),
+        }
+    }
+
+    pub(crate) fn stable_path(&self) -> String {
+        match self {
+            AsyncDeviceImpl::Sysfs(d) => d.stable_path(),
+            AsyncDeviceImpl::Ddc(d) => d.connector.stable_path(),
+        }
+    }
+
+    pub(crate) async fn capabilities_string(&self) -> Result<String, SysError> {
+        match self {
+            AsyncDeviceImpl::Sysfs(d) => Err(d.ddc_unsupported()),
+            AsyncDeviceImpl::Ddc(d) => {
+                let d = Arc::clone(d);
+                unblock(move || d.capabilities_string()).await
+            }
+        }
+    }
+
+    fn write_method(&self) -> WriteMethod {
+        match self {
+            AsyncDeviceImpl::Sysfs(d) => d.context.write_plan(&d.write_method).0,
+            AsyncDeviceImpl::Ddc(_) => WriteMethod::Ddc,
+        }
+    }
+
+    fn write_strategy(&self) -> WriteStrategy {
+        match self {
+            AsyncDeviceImpl::Sysfs(d) => d.context.write_strategy(),
+            AsyncDeviceImpl::Ddc(d) => d.context.write_strategy(),
+        }
+    }
+
+    fn set_write_strategy(&mut self, strategy: WriteStrategy) {
+        match self {
+            AsyncDeviceImpl::Sysfs(d) => d.set_write_strategy(strategy),
+            AsyncDeviceImpl::Ddc(_) => {}
+        }
+    }
+}
+
+async fn write_raw(device: &SysfsDevice, value: u32) -> Result<(), Error> {
+    let (method, fallback) = device.context.write_plan(&device.write_method);
+    match (write_with(device, method, value).await, fallback) {
+        (Err(e), Some(fallback)) => {
+            // Only stop trying a method that can't work, not one that failed once.
+            let unsupported = e.is_unsupported_write();
+            write_with(device, fallback, value)
+                .await
+                .map_err(|source| e.falling_back(&device.device, source))?;
+            if unsupported {
+                let _ = device.write_method.set(fallback);
+            }
+            Ok(())
+        }
+        (result, _) => Ok(result?),
+    }
+}
+
+async fn write_with(device: &SysfsDevice, method: WriteMethod, value: u32) -> Result<(), SysError> {
+    let class = device.class;
+    match method {
+        WriteMethod::Logind => {
+            let result = set_brightness(&device.context, class, &device.device, value).await;
+            device.context.check_logind(&device.device, result)
+        }
+        // Writing to the brightness file requires permission.
+        WriteMethod::Sysfs => {
+            let (context, device) = (device.context.clone(), device.device.clone());
+            set_value(context, class, device, value).await
+        }
+        WriteMethod::Ddc => unreachable!("backlight devices are not written through DDC/CI"),
+    }
+}
+
+impl BrightnessExt for BrightnessDevice {
+    fn write_method(&self) -> Result<WriteMethod, Error> {
+        match &self.inner {
+            DeviceImpl::Platform(d) => Ok(d.write_method()),
+            #[cfg(feature = "mock")]
+            DeviceImpl::Mock(d) => Err(d.unsupported()),
+        }
+    }
+
+    fn write_strategy(&self) -> Result<WriteStrategy, Error> {
+        match &self.inner {
+            DeviceImpl::Platform(d) => Ok(d.write_strategy()),
+            #[cfg(feature = "mock")]
+            DeviceImpl::Mock(d) => Err(d.unsupported()),
+        }
+    }
+
+    fn set_write_strategy(&mut self, strategy: WriteStrategy) -> Result<(), Error> {
+        match &mut self.inner {
+            DeviceImpl::Platform(d) => {
+                d.set_write_strategy(strategy);
                 Ok(())
             }
-            Err(e) => Err(Error::SettingBrightness {
-                device: self.device.clone(),
-                source: e.into(),
-            }),
+            #[cfg(feature = "mock")]
+            DeviceImpl::Mock(d) => Err(d.unsupported()),
+        }
+    }
+
+    fn power_state(&self) -> Result<PowerState, Error> {
+        match &self.inner {
+            DeviceImpl::Platform(d) => Ok(d.power_state()?),
+            #[cfg(feature = "mock")]
+            DeviceImpl::Mock(d) => Err(d.unsupported()),
+        }
+    }
+
+    fn set_power(&self, state: PowerState) -> Result<(), Error> {
+        match &self.inner {
+            DeviceImpl::Platform(d) => d.set_power(state),
+            #[cfg(feature = "mock")]
+            DeviceImpl::Mock(d) => Err(d.unsupported()),
         }
     }
 }
 
-pub(crate) fn brightness_devices() -> impl Stream<Item = Result<AsyncDeviceImpl, SysError>> {
-    match std::fs::read_dir(BACKLIGHT_DIR) {
-        Ok(devices) => futures::stream::iter(
-            devices
-                .map(|device| {
-                    let device = device.map_err(SysError::ReadingBacklightDir)?;
-                    let path = device.path();
-                    let keep = path.join(Value::Actual.as_str()).exists()
-                        && path.join(Value::Max.as_str()).exists();
-                    Ok(device
-                        .file_name()
-                        .into_string()
-                        .ok()
-                        .map(|device| AsyncDeviceImpl { device })
-                        .filter(|_| keep))
-                })
-                .filter_map(Result::transpose),
+/// Asks logind to set the brightness of a device, reconnecting to the system bus once if the shared
+/// connection was lost.
+async fn set_brightness(
+    context: &Context,
+    class: DeviceClass,
+    device: &str,
+    value: u32,
+) -> zbus::Result<()> {
+    let connection = match context.dbus_connection() {
+        Some(connection) => {
+            return call_set_brightness(context, connection, class, device, value).await;
+        }
+        None => system_bus(context).await?,
+    };
+    match call_set_brightness(context, &connection, class, device, value).await {
+        Err(zbus::Error::InputOutput(_)) => {
+            forget_system_bus(&connection);
+            call_set_brightness(context, &system_bus(context).await?, class, device, value).await
+        }
+        result => result,
+    }
+}
+
+async fn call_set_brightness(
+    context: &Context,
+    connection: &zbus::Connection,
+    class: DeviceClass,
+    device: &str,
+    value: u32,
+) -> zbus::Result<()> {
+    connection
+        .call_method(
+            Some(USER_DBUS_NAME),
+            &session_path(context, connection).await?,
+            Some(SESSION_INTERFACE),
+            SET_BRIGHTNESS_METHOD,
+            &(class.subsystem(), device, value),
         )
-        .right_stream(),
-        Err(e) => futures::stream::once(ready(Err(SysError::ReadingBacklightDir(e)))).left_stream(),
+        .await?;
+    Ok(())
+}
+
+async fn session_path(
+    context: &Context,
+    connection: &zbus::Connection,
+) -> zbus::Result<OwnedObjectPath> {
+    let manager = async |method, arg: &str| {
+        connection
+            .call_method(
+                Some(USER_DBUS_NAME),
+                MANAGER_OBJECT_PATH,
+                Some(MANAGER_INTERFACE),
+                method,
+                &(arg,),
+            )
+            .await
+    };
+    match context.logind_session() {
+        LogindSession::Auto => Ok(auto_session_path()),
+        LogindSession::Id(id) => manager("GetSession", id).await?.body().deserialize(),
+        LogindSession::ActiveOnSeat(seat) => {
+            let seat: OwnedObjectPath = manager("GetSeat", seat).await?.body().deserialize()?;
+            let reply = connection
+                .call_method(
+                    Some(USER_DBUS_NAME),
+                    &seat,
+                    Some(PROPERTIES_INTERFACE),
+                    "Get",
+                    &(SEAT_INTERFACE, "ActiveSession"),
+                )
+                .await?;
+            active_session_path(reply.body().deserialize()?)
+        }
+        LogindSession::Pid(pid) => connection
+            .call_method(
+                Some(USER_DBUS_NAME),
+                MANAGER_OBJECT_PATH,
+                Some(MANAGER_INTERFACE),
+                "GetSessionByPID",
+                &(pid,),
+            )
+            .await?
+            .body()
+            .deserialize(),
     }
 }
 
-async fn set_value(device: String, value: u32) -> Result<(), SysError> {
-    unblock(move || crate::blocking::linux::set_value(&device, value)).await
+/// Returns the shared system bus connection, connecting if needed.
+async fn system_bus(context: &Context) -> zbus::Result<zbus::Connection> {
+    if let Some(connection) = cached_system_bus() {
+        return Ok(connection);
+    }
+    let connection = match context.system_bus_address() {
+        Some(address) => zbus::connection::Builder::address(address)?.build().await?,
+        None => zbus::Connection::system().await?,
+    };
+    Ok(share_system_bus(connection))
+}
+
+pub(crate) fn brightness_devices() -> impl Stream<Item = Result<AsyncDeviceImpl, SysError>> {
+    devices(&Context::new())
+}
+
+fn devices(context: &Context) -> impl Stream<Item = Result<AsyncDeviceImpl, SysError>> + use<> {
+    let context = context.clone();
+    // Finding monitors blocks while they are probed.
+    stream::once(unblock(move || context.devices().collect::<Vec<_>>()))
+        .flat_map(stream::iter)
+        .map(|d| d.map(AsyncDeviceImpl::from_blocking))
+}
+
+async fn set_value(
+    context: Context,
+    class: DeviceClass,
+    device: String,
+    value: u32,
+) -> Result<(), SysError> {
+    unblock(move || crate::blocking::linux::set_value(&context, class, &device, value)).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocking::linux::tests::{
+        FakeLogind, FakeSysfs, PID_SESSION_PATH, SEAT_SESSION_PATH, SESSION_PID,
+    };
+    use futures::{StreamExt, executor::block_on};
+    use std::{fs, pin::pin};
+
+    fn first_device(context: &Context) -> BrightnessDevice {
+        block_on(context.brightness_devices().next())
+            .unwrap()
+            .unwrap()
+    }
+
+    #[test]
+    fn brightness_is_set_through_logind() {
+        let sysfs = FakeSysfs::new();
+        sysfs.add_device("panel", 200, 50);
+        let logind = FakeLogind::default();
+        let mut context = sysfs.context();
+        context.set_dbus_connection(logind.connect());
+        let mut device = first_device(&context);
+        block_on(crate::Brightness::set(&mut device, 40)).unwrap();
+        assert_eq!(logind.calls(), [("backlight".into(), "panel".into(), 80)]);
+        assert_eq!(device.write_method().unwrap(), WriteMethod::Logind);
+        assert!(!sysfs.device_dir("panel").join("brightness").exists());
+    }
+
+    #[test]
+    fn sysfs_is_used_when_logind_cannot_set_brightness() {
+        let sysfs = FakeSysfs::new();
+        sysfs.add_device("panel", 200, 50);
+        let logind = FakeLogind::outdated();
+        let mut context = sysfs.context();
+        context.set_dbus_connection(logind.connect());
+        let mut device = first_device(&context);
+        block_on(crate::Brightness::set(&mut device, 40)).unwrap();
+        let written = fs::read_to_string(sysfs.device_dir("panel").join("brightness")).unwrap();
+        assert_eq!(written, "80");
+        assert_eq!(device.write_method().unwrap(), WriteMethod::Sysfs);
+    }
+
+    #[test]
+    fn lost_system_bus_connection_is_replaced() {
+        let logind = FakeLogind::default();
+        let bus = logind.serve_system_bus();
+        let context = bus.context();
+        block_on(set_brightness(&context, DeviceClass::Backlight, "panel", 1)).unwrap();
+        let first = cached_system_bus().unwrap();
+        logind.disconnect();
+        block_on(set_brightness(&context, DeviceClass::Backlight, "panel", 2)).unwrap();
+        let second = cached_system_bus().unwrap();
+        assert_ne!(first.unique_name(), second.unique_name());
+        let values = logind
+            .calls()
+            .into_iter()
+            .map(|(.., v)| v)
+            .collect::<Vec<_>>();
+        assert_eq!(values, [1, 2]);
+    }
+
+    #[test]
+    fn sessions_are_looked_up_before_setting_brightness() {
+        let sysfs = FakeSysfs::new();
+        sysfs.add_device("panel", 200, 50);
+        let sessions = [
+            (LogindSession::Id("c3".into()), PID_SESSION_PATH),
+            (
+                LogindSession::ActiveOnSeat("seat0".into()),
+                SEAT_SESSION_PATH,
+            ),
+            (LogindSession::Pid(SESSION_PID), PID_SESSION_PATH),
+        ];
+        for (session, path) in sessions {
+            let logind = FakeLogind::default();
+            let mut context = sysfs.context();
+            context.set_dbus_connection(logind.connect());
+            context.set_logind_session(session.clone());
+            let mut device = first_device(&context);
+            block_on(crate::Brightness::set(&mut device, 40)).unwrap();
+            let expected = [("backlight".into(), "panel".into(), 80)];
+            assert_eq!(logind.calls_to(path), expected, "{session:?}");
+            assert_eq!(logind.calls(), [], "{session:?}");
+        }
+    }
+
+    #[test]
+    fn monitors_report_watching_as_unsupported() {
+        let monitor = crate::blocking::linux::tests::silent_monitor(5);
+        let inner = AsyncDeviceImpl::Ddc(Arc::new(monitor));
+        let device = BrightnessDevice::new(DeviceImpl::Platform(inner));
+        let mut changes = pin!(device.watch());
+        match block_on(changes.next()) {
+            Some(Err(Error::GettingDeviceInfo { device, .. })) => assert_eq!(device, "i2c-5"),
+            other => panic!("expected an error, got {other:?}"),
+        }
+        assert!(block_on(changes.next()).is_none());
+    }
 }
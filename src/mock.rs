@@ -0,0 +1,553 @@
+// Copyright (C) 2022 The brightness project authors. Distributed under the 0BSD license.
+
+//! In-memory brightness devices for testing.
+//!
+//! [`MockBackend`] holds a programmable set of virtual devices. The devices it enumerates are
+//! regular [`BrightnessDevice`]s, so code written against the
+//! [`Brightness`](crate::blocking::Brightness) traits can be tested without real hardware.
+//!
+//! # Example
+//!
+//! ```rust
+//! use brightness::{blocking::Brightness, mock::MockBackend};
+//!
+//! let backend = MockBackend::new().with_device("panel", 200);
+//! let dev = backend.blocking_brightness_devices().next().unwrap().unwrap();
+//! dev.set(25).unwrap();
+//! assert_eq!(backend.value("panel"), Some(50));
+//!
+//! backend.fail_setting("panel", true);
+//! assert!(matches!(dev.set(50), Err(brightness::Error::SettingBrightness { .. })));
+//! ```
+
+use crate::{
+    DeviceEvent, DeviceType, Error, Scale, VcpValue,
+    blocking::{BrightnessDevice, DeviceImpl},
+    conversion::{percentage_to_raw, raw_to_percentage},
+    hotplug::{self, Source},
+    watch::Watcher,
+};
+use std::{
+    sync::{Arc, Condvar, Mutex, MutexGuard},
+    time::Duration,
+};
+
+/// Programmable set of virtual brightness devices.
+///
+/// Clones share the same devices, so a backend kept by a test can be used to inspect and alter the
+/// state seen by devices handed to the code under test.
+#[derive(Clone, Debug, Default)]
+pub struct MockBackend {
+    shared: Arc<Shared>,
+}
+
+#[derive(Debug, Default)]
+struct Shared {
+    state: Mutex<State>,
+    changed: Condvar,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    devices: Vec<DeviceState>,
+    fail_listing: bool,
+    /// Incremented whenever a brightness value changes or a device is added or removed.
+    generation: u64,
+}
+
+#[derive(Debug)]
+struct DeviceState {
+    name: String,
+    value: u32,
+    max: u32,
+    scale: Scale,
+    device_type: DeviceType,
+    vcp: Vec<(u8, VcpValue)>,
+    capabilities: Option<String>,
+    edid: Option<Vec<u8>>,
+    connector: Option<String>,
+    internal: bool,
+    fail_getting: bool,
+    fail_setting: bool,
+}
+
+impl State {
+    fn device(&self, name: &str) -> Option<&DeviceState> {
+        self.devices.iter().find(|d| d.name == name)
+    }
+
+    fn device_mut(&mut self, name: &str) -> Option<&mut DeviceState> {
+        self.devices.iter_mut().find(|d| d.name == name)
+    }
+}
+
+impl MockBackend {
+    /// Returns a backend without any device.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a device at full brightness and returns the backend.
+    pub fn with_device<S: Into<String>>(self, name: S, max: u32) -> Self {
+        self.add_device(name, max);
+        self
+    }
+
+    /// Adds a device at full brightness. An existing device with the same name is replaced.
+    pub fn add_device<S: Into<String>>(&self, name: S, max: u32) {
+        let name = name.into();
+        let mut state = self.lock();
+        state.devices.retain(|d| d.name != name);
+        state.devices.push(DeviceState {
+            name,
+            value: max,
+            max,
+            scale: Scale::Unknown,
+            device_type: DeviceType::Unknown,
+            vcp: Vec::new(),
+            capabilities: None,
+            edid: None,
+            connector: None,
+            internal: false,
+            fail_getting: false,
+            fail_setting: false,
+        });
+        self.notify(&mut state);
+    }
+
+    /// Removes a device. Returns whether the device existed.
+    ///
+    /// Devices previously enumerated fail to get device info or set brightness afterwards.
+    pub fn remove_device(&self, name: &str) -> bool {
+        let mut state = self.lock();
+        let len = state.devices.len();
+        state.devices.retain(|d| d.name != name);
+        self.notify(&mut state);
+        state.devices.len() != len
+    }
+
+    /// Returns the raw brightness value of a device.
+    pub fn value(&self, name: &str) -> Option<u32> {
+        self.lock().device(name).map(|d| d.value)
+    }
+
+    /// Changes the raw brightness value of a device, as if it were done by another program. The
+    /// value is clamped to the device maximum. Returns whether the device exists.
+    pub fn set_value(&self, name: &str, value: u32) -> bool {
+        let mut state = self.lock();
+        let found = state
+            .device_mut(name)
+            .map(|d| d.value = value.min(d.max))
+            .is_some();
+        self.notify(&mut state);
+        found
+    }
+
+    /// Changes the scale reported by a device. Devices are added with an unknown scale. Returns
+    /// whether the device exists.
+    pub fn set_scale(&self, name: &str, scale: Scale) -> bool {
+        self.lock()
+            .device_mut(name)
+            .map(|d| d.scale = scale)
+            .is_some()
+    }
+
+    /// Changes the type reported by a device. Devices are added with an unknown type. Returns
+    /// whether the device exists.
+    pub fn set_device_type(&self, name: &str, device_type: DeviceType) -> bool {
+        self.lock()
+            .device_mut(name)
+            .map(|d| d.device_type = device_type)
+            .is_some()
+    }
+
+    /// Makes a device support a VCP feature with the given value, or changes the value if the
+    /// feature is already supported. Devices are added without VCP features. Returns whether the
+    /// device exists.
+    ///
+    /// ```rust
+    /// use brightness::{InputSource, VcpValue, blocking::MonitorControl, mock::MockBackend};
+    ///
+    /// let backend = MockBackend::new().with_device("monitor", 100);
+    /// backend.set_vcp_feature("monitor", 0x60, VcpValue { current: 0x0f, max: 0 });
+    /// let dev = backend.blocking_brightness_devices().next().unwrap().unwrap();
+    /// assert_eq!(dev.input_source().unwrap(), InputSource::DisplayPort1);
+    /// dev.set_input_source(InputSource::Hdmi1).unwrap();
+    /// assert_eq!(backend.vcp_feature("monitor", 0x60).unwrap().current, 0x11);
+    /// assert!(dev.contrast().is_err());
+    /// ```
+    pub fn set_vcp_feature(&self, name: &str, code: u8, value: VcpValue) -> bool {
+        self.lock()
+            .device_mut(name)
+            .map(|d| match d.vcp.iter_mut().find(|(c, _)| *c == code) {
+                Some((_, v)) => *v = value,
+                None => d.vcp.push((code, value)),
+            })
+            .is_some()
+    }
+
+    /// Returns the value of a VCP feature of a device, if the device supports it.
+    pub fn vcp_feature(&self, name: &str, code: u8) -> Option<VcpValue> {
+        let state = self.lock();
+        let device = state.device(name)?;
+        device.vcp.iter().find(|(c, _)| *c == code).map(|&(_, v)| v)
+    }
+
+    /// Sets the MCCS capabilities string reported by a device. Devices are added without
+    /// capabilities string. Returns whether the device exists.
+    pub fn set_capabilities<S: Into<String>>(&self, name: &str, capabilities: S) -> bool {
+        self.lock()
+            .device_mut(name)
+            .map(|d| d.capabilities = Some(capabilities.into()))
+            .is_some()
+    }
+
+    /// Changes the connector name reported by a device. Devices are added without connector.
+    /// Returns whether the device exists.
+    pub fn set_connector_name<S: Into<String>>(&self, name: &str, connector: Option<S>) -> bool {
+        self.lock()
+            .device_mut(name)
+            .map(|d| d.connector = connector.map(Into::into))
+            .is_some()
+    }
+
+    /// Changes whether a device reports controlling a built-in panel. Devices are added as
+    /// external displays. Returns whether the device exists.
+    pub fn set_internal(&self, name: &str, internal: bool) -> bool {
+        self.lock()
+            .device_mut(name)
+            .map(|d| d.internal = internal)
+            .is_some()
+    }
+
+    /// Sets the EDID of the display of a device. Devices are added without EDID, i.e. their
+    /// display can't be identified. Returns whether the device exists.
+    pub fn set_edid<E: Into<Vec<u8>>>(&self, name: &str, edid: E) -> bool {
+        self.lock()
+            .device_mut(name)
+            .map(|d| d.edid = Some(edid.into()))
+            .is_some()
+    }
+
+    /// Makes enumeration fail with [`Error::ListingDevices`].
+    pub fn fail_listing(&self, fail: bool) {
+        self.lock().fail_listing = fail;
+    }
+
+    /// Makes getting brightness or VCP features of a device fail with
+    /// [`Error::GettingDeviceInfo`]. Returns whether the device exists.
+    pub fn fail_getting(&self, name: &str, fail: bool) -> bool {
+        self.lock()
+            .device_mut(name)
+            .map(|d| d.fail_getting = fail)
+            .is_some()
+    }
+
+    /// Makes setting brightness of a device fail with [`Error::SettingBrightness`], and setting VCP
+    /// features with [`Error::SettingVcpFeature`]. Returns whether the device exists.
+    pub fn fail_setting(&self, name: &str, fail: bool) -> bool {
+        self.lock()
+            .device_mut(name)
+            .map(|d| d.fail_setting = fail)
+            .is_some()
+    }
+
+    /// Blocking function that returns all devices of this backend.
+    pub fn blocking_brightness_devices(
+        &self,
+    ) -> impl Iterator<Item = Result<BrightnessDevice, Error>> + use<> {
+        self.devices()
+            .into_iter()
+            .map(|r| r.map(|d| BrightnessDevice::new(DeviceImpl::Mock(d))))
+    }
+
+    /// Returns all devices of this backend.
+    #[cfg(feature = "async")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+    pub fn brightness_devices(
+        &self,
+    ) -> impl futures::Stream<Item = Result<crate::BrightnessDevice, Error>> + use<> {
+        futures::stream::iter(
+            self.devices().into_iter().map(|r| {
+                r.map(|d| crate::BrightnessDevice::new(crate::r#async::DeviceImpl::Mock(d)))
+            }),
+        )
+    }
+
+    fn devices(&self) -> Vec<Result<MockDevice, Error>> {
+        let state = self.lock();
+        if state.fail_listing {
+            return vec![Err(SysError::Listing.into())];
+        }
+        state
+            .devices
+            .iter()
+            .map(|d| {
+                Ok(MockDevice {
+                    backend: self.clone(),
+                    name: d.name.clone(),
+                })
+            })
+            .collect()
+    }
+
+    /// Blocking function that returns an iterator over device events, starting with an
+    /// [`Added`](DeviceEvent::Added) event for each existing device.
+    pub fn blocking_device_events(
+        &self,
+    ) -> impl Iterator<Item = Result<DeviceEvent<BrightnessDevice>, Error>> + use<> {
+        hotplug::blocking_events(Source::Mock(self.clone()))
+    }
+
+    /// Returns a stream of device events, starting with an [`Added`](DeviceEvent::Added) event
+    /// for each existing device.
+    #[cfg(feature = "async")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+    pub fn device_events(
+        &self,
+    ) -> impl futures::Stream<Item = Result<DeviceEvent<crate::BrightnessDevice>, Error>> + use<>
+    {
+        hotplug::events(Source::Mock(self.clone()))
+    }
+
+    pub(crate) fn watcher(&self) -> MockWatcher {
+        MockWatcher {
+            backend: self.clone(),
+            generation: self.lock().generation,
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.shared.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn notify(&self, state: &mut State) {
+        state.generation += 1;
+        self.shared.changed.notify_all();
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct MockDevice {
+    backend: MockBackend,
+    name: String,
+}
+
+impl MockDevice {
+    pub(crate) fn scale(&self) -> Scale {
+        self.backend
+            .lock()
+            .device(&self.name)
+            .map_or(Scale::Unknown, |d| d.scale)
+    }
+
+    pub(crate) fn watcher(&self) -> Result<Watcher, SysError> {
+        Ok(Watcher::Mock(self.backend.watcher()))
+    }
+
+    pub(crate) fn device_type(&self) -> DeviceType {
+        self.backend
+            .lock()
+            .device(&self.name)
+            .map_or(DeviceType::Unknown, |d| d.device_type)
+    }
+
+    pub(crate) fn connector_name(&self) -> Option<String> {
+        self.backend.lock().device(&self.name)?.connector.clone()
+    }
+
+    pub(crate) fn is_internal(&self) -> bool {
+        self.backend
+            .lock()
+            .device(&self.name)
+            .is_some_and(|d| d.internal)
+    }
+
+    pub(crate) fn driver(&self) -> String {
+        "mock".into()
+    }
+
+    pub(crate) fn stable_path(&self) -> String {
+        self.name.clone()
+    }
+
+    /// Returns the error reported by platform-specific functionality that mock devices lack.
+    pub(crate) fn unsupported(&self) -> Error {
+        SysError::Unsupported {
+            device: self.name.clone(),
+        }
+        .into()
+    }
+}
+
+impl crate::blocking::Brightness for MockDevice {
+    fn device_name(&self) -> Result<String, Error> {
+        Ok(self.name.clone())
+    }
+
+    fn get(&self) -> Result<u32, Error> {
+        let state = self.backend.lock();
+        let device = self.readable(&state)?;
+        Ok(raw_to_percentage(device.value, device.max))
+    }
+
+    fn set(&self, percentage: u32) -> Result<(), Error> {
+        let mut state = self.backend.lock();
+        let device = self.writable(&mut state)?;
+        device.value = percentage_to_raw(percentage, device.max);
+        self.backend.notify(&mut state);
+        Ok(())
+    }
+
+    fn get_raw(&self) -> Result<u32, Error> {
+        Ok(self.readable(&self.backend.lock())?.value)
+    }
+
+    fn max_raw(&self) -> Result<u32, Error> {
+        Ok(self.readable(&self.backend.lock())?.max)
+    }
+
+    fn set_raw(&self, value: u32) -> Result<(), Error> {
+        let mut state = self.backend.lock();
+        let device = self.writable(&mut state)?;
+        device.value = value.min(device.max);
+        self.backend.notify(&mut state);
+        Ok(())
+    }
+}
+
+impl MockDevice {
+    pub(crate) fn get_vcp(&self, code: u8) -> Result<VcpValue, SysError> {
+        let state = self.backend.lock();
+        let device = self.readable(&state)?;
+        device
+            .vcp
+            .iter()
+            .find(|(c, _)| *c == code)
+            .map(|&(_, v)| v)
+            .ok_or_else(|| SysError::UnsupportedFeature {
+                device: self.name.clone(),
+                code,
+            })
+    }
+
+    pub(crate) fn set_vcp(&self, code: u8, value: u16) -> Result<(), SysError> {
+        let mut state = self.backend.lock();
+        let device = self.writable(&mut state).map_err(|e| match e {
+            SysError::Setting { device } => SysError::SettingVcpFeature { device, code },
+            e => e,
+        })?;
+        let (_, v) = device
+            .vcp
+            .iter_mut()
+            .find(|(c, _)| *c == code)
+            .ok_or_else(|| SysError::UnsupportedFeature {
+                device: self.name.clone(),
+                code,
+            })?;
+        v.current = value;
+        Ok(())
+    }
+
+    pub(crate) fn edid(&self) -> Result<Option<Vec<u8>>, SysError> {
+        Ok(self.readable(&self.backend.lock())?.edid.clone())
+    }
+
+    pub(crate) fn capabilities_string(&self) -> Result<String, SysError> {
+        let state = self.backend.lock();
+        self.readable(&state)?
+            .capabilities
+            .clone()
+            .ok_or_else(|| SysError::Unsupported {
+                device: self.name.clone(),
+            })
+    }
+
+    fn readable<'a>(&self, state: &'a State) -> Result<&'a DeviceState, SysError> {
+        let device = state.device(&self.name).ok_or_else(|| SysError::Missing {
+            device: self.name.clone(),
+        })?;
+        if device.fail_getting {
+            return Err(SysError::Getting {
+                device: self.name.clone(),
+            });
+        }
+        Ok(device)
+    }
+
+    fn writable<'a>(&self, state: &'a mut State) -> Result<&'a mut DeviceState, SysError> {
+        let device = state
+            .device_mut(&self.name)
+            .ok_or_else(|| SysError::Missing {
+                device: self.name.clone(),
+            })?;
+        if device.fail_setting {
+            return Err(SysError::Setting {
+                device: self.name.clone(),
+            });
+        }
+        Ok(device)
+    }
+}
+
+/// Waits for changes made to the devices of a mock backend.
+#[derive(Debug)]
+pub(crate) struct MockWatcher {
+    backend: MockBackend,
+    generation: u64,
+}
+
+impl MockWatcher {
+    pub(crate) fn wait(&mut self, timeout: Duration) {
+        let state = self.backend.lock();
+        let (state, _) = self
+            .backend
+            .shared
+            .changed
+            .wait_timeout_while(state, timeout, |s| s.generation == self.generation)
+            .unwrap_or_else(|e| e.into_inner());
+        self.generation = state.generation;
+    }
+}
+
+#[derive(Debug, Error)]
+pub(crate) enum SysError {
+    #[error("Injected failure listing mock devices")]
+    Listing,
+    #[error("Mock device {device} was removed")]
+    Missing { device: String },
+    #[error("Injected failure getting mock device {device} information")]
+    Getting { device: String },
+    #[error("Injected failure setting mock device {device} brightness")]
+    Setting { device: String },
+    #[error("Injected failure setting VCP feature {code:#04x} of mock device {device}")]
+    SettingVcpFeature { device: String, code: u8 },
+    #[error("Mock device {device} does not support VCP feature {code:#04x}")]
+    UnsupportedFeature { device: String, code: u8 },
+    #[error("Mock device {device} does not support this operation")]
+    Unsupported { device: String },
+}
+
+impl From<SysError> for Error {
+    fn from(e: SysError) -> Self {
+        match &e {
+            SysError::Listing => Error::ListingDevices(e.into()),
+            SysError::Missing { device }
+            | SysError::Getting { device }
+            | SysError::Unsupported { device }
+            | SysError::UnsupportedFeature { device, .. } => Error::GettingDeviceInfo {
+                device: device.clone(),
+                source: e.into(),
+            },
+            SysError::Setting { device } => Error::SettingBrightness {
+                device: device.clone(),
+                source: e.into(),
+            },
+            SysError::SettingVcpFeature { device, code } => Error::SettingVcpFeature {
+                device: device.clone(),
+                code: *code,
+                source: e.into(),
+            },
+        }
+    }
+}
@@ -0,0 +1,152 @@
+// Copyright (C) 2022 The brightness project authors. Distributed under the 0BSD license.
+
+//! Perceptual brightness mapping.
+
+use crate::Level;
+
+/// CIE 1976 constants, in their exact form so that both sides of the breakpoint meet at a
+/// lightness of 8.
+const CIE_EPSILON: f64 = 216.0 / 24389.0;
+const CIE_KAPPA: f64 = 24389.0 / 27.0;
+
+/// Mapping between requested brightness and the light output of a device.
+///
+/// With a perceptual mapping, percentages and levels describe perceived brightness, so that 50%
+/// looks half as bright as 100%. Raw values are never mapped.
+///
+/// The mapping only applies to devices whose [`Scale`] is not [`Scale::NonLinear`], as those
+/// already account for perception.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Perception {
+    /// Brightness maps linearly to device values.
+    #[default]
+    Linear,
+    /// Brightness maps to device values using CIE 1931 lightness.
+    Cie1931,
+    /// Brightness maps to device values raised to the given power. Values that are not finite and
+    /// strictly positive behave like [`Perception::Linear`].
+    Gamma(f64),
+}
+
+impl Perception {
+    /// Returns the linear level for a perceived level.
+    pub(crate) fn linearize(self, level: Level) -> Level {
+        let l = level.fraction();
+        let linear = match self {
+            Perception::Linear => l,
+            Perception::Cie1931 => {
+                let lightness = l * 100.0;
+                if lightness > 8.0 {
+                    ((lightness + 16.0) / 116.0).powi(3)
+                } else {
+                    lightness / CIE_KAPPA
+                }
+            }
+            Perception::Gamma(gamma) if gamma.is_finite() && gamma > 0.0 => l.powf(gamma),
+            Perception::Gamma(_) => l,
+        };
+        Level::from_fraction_clamped(linear)
+    }
+
+    /// Returns the perceived level for a linear level.
+    pub(crate) fn perceive(self, level: Level) -> Level {
+        let y = level.fraction();
+        let perceived = match self {
+            Perception::Linear => y,
+            Perception::Cie1931 => {
+                let lightness = if y > CIE_EPSILON {
+                    116.0 * y.cbrt() - 16.0
+                } else {
+                    CIE_KAPPA * y
+                };
+                lightness / 100.0
+            }
+            Perception::Gamma(gamma) if gamma.is_finite() && gamma > 0.0 => y.powf(gamma.recip()),
+            Perception::Gamma(_) => y,
+        };
+        Level::from_fraction_clamped(perceived)
+    }
+}
+
+/// How device values relate to light output, as reported by the device.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum Scale {
+    /// Light output is proportional to the device value.
+    Linear,
+    /// The device value already accounts for perception.
+    NonLinear,
+    /// The device does not report its scale.
+    #[default]
+    Unknown,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAPPINGS: [Perception; 4] = [
+        Perception::Linear,
+        Perception::Cie1931,
+        Perception::Gamma(2.2),
+        Perception::Gamma(0.5),
+    ];
+
+    fn level(fraction: f64) -> Level {
+        Level::new(fraction).unwrap()
+    }
+
+    fn assert_close(actual: Level, expected: f64) {
+        let error = (actual.fraction() - expected).abs();
+        assert!(error < 1e-9, "{actual:?} instead of {expected}");
+    }
+
+    #[test]
+    fn ends_map_to_themselves() {
+        for p in MAPPINGS {
+            assert_eq!(p.linearize(Level::MIN), Level::MIN, "{p:?}");
+            assert_eq!(p.perceive(Level::MIN), Level::MIN, "{p:?}");
+            assert_close(p.linearize(Level::MAX), 1.0);
+            assert_close(p.perceive(Level::MAX), 1.0);
+        }
+    }
+
+    #[test]
+    fn levels_round_trip() {
+        // Both sides of the CIE 1931 breakpoint at a lightness of 8, i.e. a luminance of 0.008856.
+        let fractions = [0.001, 0.079, 0.08, 0.081, 0.25, 0.5, 0.75, 0.999];
+        for p in MAPPINGS {
+            for fraction in fractions {
+                assert_close(p.perceive(p.linearize(level(fraction))), fraction);
+                assert_close(p.linearize(p.perceive(level(fraction))), fraction);
+            }
+        }
+    }
+
+    #[test]
+    fn cie1931_is_continuous_at_the_breakpoint() {
+        let below = Perception::Cie1931.linearize(level(0.08)).fraction();
+        let above = Perception::Cie1931.linearize(level(0.080_001)).fraction();
+        assert!((above - below).abs() < 1e-6, "{below} {above}");
+        assert_close(Perception::Cie1931.perceive(level(CIE_EPSILON)), 0.08);
+        assert_close(Perception::Cie1931.linearize(level(0.08)), CIE_EPSILON);
+    }
+
+    #[test]
+    fn perceived_mappings_dim_the_middle() {
+        assert_close(
+            Perception::Cie1931.linearize(level(0.5)),
+            0.184_186_518_512_444_2,
+        );
+        assert_close(Perception::Gamma(2.0).linearize(level(0.5)), 0.25);
+        assert_close(Perception::Gamma(2.0).perceive(level(0.25)), 0.5);
+    }
+
+    #[test]
+    fn invalid_gammas_are_linear() {
+        for gamma in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+            let p = Perception::Gamma(gamma);
+            assert_close(p.linearize(level(0.3)), 0.3);
+            assert_close(p.perceive(level(0.3)), 0.3);
+        }
+    }
+}
@@ -0,0 +1,245 @@
+// Copyright (C) 2022 The brightness project authors. Distributed under the 0BSD license.
+
+//! Animated brightness transitions.
+
+use crate::{Level, Perception};
+use std::time::Duration;
+
+/// Shortest interval between two steps of a transition.
+const MIN_STEP_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Progression of a brightness transition over time.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum Easing {
+    /// Constant speed.
+    #[default]
+    Linear,
+    /// Starts slowly and accelerates.
+    EaseIn,
+    /// Starts quickly and decelerates.
+    EaseOut,
+    /// Starts and ends slowly.
+    EaseInOut,
+}
+
+impl Easing {
+    /// Returns the progress of the transition for a fraction of its duration, both between 0.0
+    /// and 1.0.
+    fn apply(self, t: f64) -> f64 {
+        match self {
+            Easing::Linear => t,
+            Easing::EaseIn => t.powi(3),
+            Easing::EaseOut => 1.0 - (1.0 - t).powi(3),
+            Easing::EaseInOut if t < 0.5 => 4.0 * t.powi(3),
+            Easing::EaseInOut => 1.0 - (2.0 - 2.0 * t).powi(3) / 2.0,
+        }
+    }
+}
+
+/// Raw values to write during a transition.
+///
+/// Levels are interpolated as requested, i.e. perceptually if a perception correction applies,
+/// then converted to raw values. Steps that would not change the raw value are skipped, and the
+/// last step is always the target.
+#[derive(Debug)]
+pub(crate) struct Steps {
+    from: Level,
+    to: Level,
+    max: u32,
+    correction: Option<Perception>,
+    easing: Easing,
+    duration: Duration,
+    count: u32,
+    step: u32,
+    last: u32,
+}
+
+impl Steps {
+    pub(crate) fn new(
+        from: Level,
+        to: Level,
+        max: u32,
+        correction: Option<Perception>,
+        easing: Easing,
+        duration: Duration,
+    ) -> Self {
+        let mut steps = Self {
+            from,
+            to,
+            max,
+            correction,
+            easing,
+            duration,
+            count: 0,
+            step: 0,
+            last: 0,
+        };
+        steps.last = steps.raw(from);
+        let distance = steps.raw(to).abs_diff(steps.last);
+        let intervals = duration.as_nanos() / MIN_STEP_INTERVAL.as_nanos();
+        steps.count = u32::try_from(intervals)
+            .unwrap_or(u32::MAX)
+            .clamp(1, distance.max(1));
+        steps
+    }
+
+    fn raw(&self, level: Level) -> u32 {
+        let level = match self.correction {
+            Some(perception) => perception.linearize(level),
+            None => level,
+        };
+        level.to_raw(self.max)
+    }
+
+    /// Returns the next raw value to write and when to write it, relative to the start of the
+    /// transition.
+    pub(crate) fn next(&mut self) -> Option<(Duration, u32)> {
+        while self.step < self.count {
+            self.step += 1;
+            let t = f64::from(self.step) / f64::from(self.count);
+            let level = if self.step == self.count {
+                self.to
+            } else {
+                let (from, to) = (self.from.fraction(), self.to.fraction());
+                Level::from_fraction_clamped(from + (to - from) * self.easing.apply(t))
+            };
+            let raw = self.raw(level);
+            if raw != self.last || self.step == self.count {
+                self.last = raw;
+                return Some((self.duration.mul_f64(t), raw));
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EASINGS: [Easing; 4] = [
+        Easing::Linear,
+        Easing::EaseIn,
+        Easing::EaseOut,
+        Easing::EaseInOut,
+    ];
+
+    fn collect(mut steps: Steps) -> Vec<(Duration, u32)> {
+        let mut all = Vec::new();
+        while let Some(step) = steps.next() {
+            all.push(step);
+        }
+        all
+    }
+
+    #[test]
+    fn easings_start_at_zero_and_end_at_one() {
+        for easing in EASINGS {
+            assert_eq!(easing.apply(0.0), 0.0, "{easing:?}");
+            assert_eq!(easing.apply(1.0), 1.0, "{easing:?}");
+            let mut last = 0.0;
+            for i in 1..=100 {
+                let progress = easing.apply(f64::from(i) / 100.0);
+                assert!(progress >= last, "{easing:?} at {i}%");
+                last = progress;
+            }
+        }
+    }
+
+    #[test]
+    fn easings_shape_the_middle() {
+        assert_eq!(Easing::Linear.apply(0.25), 0.25);
+        assert!(Easing::EaseIn.apply(0.25) < 0.25);
+        assert!(Easing::EaseOut.apply(0.25) > 0.25);
+        assert!(Easing::EaseInOut.apply(0.25) < 0.25);
+        assert!(Easing::EaseInOut.apply(0.75) > 0.75);
+        assert!((Easing::EaseInOut.apply(0.5) - 0.5).abs() < 1e-12);
+    }
+
+    #[test]
+    fn steps_are_limited_by_the_step_interval() {
+        let steps = Steps::new(
+            Level::MIN,
+            Level::MAX,
+            1000,
+            None,
+            Easing::Linear,
+            Duration::from_secs(1),
+        );
+        let steps = collect(steps);
+        assert_eq!(steps.len(), 100);
+        assert_eq!(steps[0], (MIN_STEP_INTERVAL, 10));
+        assert_eq!(steps[99], (Duration::from_secs(1), 1000));
+        assert!(steps.windows(2).all(|w| w[0].0 < w[1].0 && w[0].1 < w[1].1));
+    }
+
+    #[test]
+    fn steps_are_limited_by_the_raw_distance() {
+        let steps = Steps::new(
+            Level::MIN,
+            Level::MAX,
+            3,
+            None,
+            Easing::Linear,
+            Duration::from_secs(1),
+        );
+        let raws = collect(steps).into_iter().map(|s| s.1).collect::<Vec<_>>();
+        assert_eq!(raws, [1, 2, 3]);
+
+        let steps = Steps::new(
+            Level::MAX,
+            Level::MIN,
+            3,
+            None,
+            Easing::Linear,
+            Duration::from_secs(1),
+        );
+        let raws = collect(steps).into_iter().map(|s| s.1).collect::<Vec<_>>();
+        assert_eq!(raws, [2, 1, 0]);
+    }
+
+    #[test]
+    fn unchanged_raw_values_are_skipped() {
+        for easing in EASINGS {
+            let steps = Steps::new(
+                Level::MIN,
+                Level::MAX,
+                1000,
+                None,
+                easing,
+                Duration::from_secs(1),
+            );
+            let steps = collect(steps);
+            // Only the target may repeat the previous value, as it is always written.
+            let (_, before) = steps.split_last().unwrap();
+            assert!(before.windows(2).all(|w| w[0].1 != w[1].1), "{easing:?}");
+            assert_eq!(steps.last(), Some(&(Duration::from_secs(1), 1000)));
+        }
+    }
+
+    #[test]
+    fn short_or_empty_transitions_write_the_target_once() {
+        let half = Level::from_percentage(50).unwrap();
+        for (from, duration) in [(Level::MIN, Duration::ZERO), (half, Duration::from_secs(1))] {
+            let steps = Steps::new(from, half, 100, None, Easing::Linear, duration);
+            assert_eq!(collect(steps), [(duration, 50)]);
+        }
+    }
+
+    #[test]
+    fn steps_are_interpolated_perceptually() {
+        let steps = Steps::new(
+            Level::MIN,
+            Level::MAX,
+            1000,
+            Some(Perception::Cie1931),
+            Easing::Linear,
+            Duration::from_secs(1),
+        );
+        let steps = collect(steps);
+        assert_eq!(steps.last(), Some(&(Duration::from_secs(1), 1000)));
+        // Halfway through, the panel is at half the perceived brightness.
+        let middle = steps.iter().find(|s| s.0 >= Duration::from_millis(500));
+        assert_eq!(middle.map(|s| s.1), Some(184));
+    }
+}
@@ -0,0 +1,227 @@
+// Copyright (C) 2022 The brightness project authors. Distributed under the 0BSD license.
+
+//! Monitor settings accessed through VCP (Virtual Control Panel) features, as defined by VESA MCCS.
+
+/// VCP code of the luminance feature.
+#[cfg(target_os = "linux")]
+pub(crate) const LUMINANCE: u8 = 0x10;
+/// VCP code of the contrast feature.
+pub(crate) const CONTRAST: u8 = 0x12;
+/// VCP code of the color preset feature.
+pub(crate) const COLOR_PRESET: u8 = 0x14;
+/// VCP code of the input source feature.
+pub(crate) const INPUT_SOURCE: u8 = 0x60;
+/// VCP code of the audio speaker volume feature.
+pub(crate) const VOLUME: u8 = 0x62;
+/// VCP code of the power mode feature.
+pub(crate) const POWER_MODE: u8 = 0xd6;
+
+/// Current and maximum values of a VCP feature.
+///
+/// For features with a set of possible values instead of a continuous range, `current` holds the
+/// selected value in its low byte.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct VcpValue {
+    /// Current value.
+    pub current: u16,
+    /// Maximum value.
+    pub max: u16,
+}
+
+impl VcpValue {
+    /// Returns the value selected for features with a set of possible values.
+    pub(crate) fn selected(self) -> u8 {
+        self.current.to_le_bytes()[0]
+    }
+}
+
+/// Video input of a monitor (VCP code `0x60`).
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum InputSource {
+    /// First analog video (VGA) input.
+    Vga1,
+    /// Second analog video (VGA) input.
+    Vga2,
+    /// First DVI input.
+    Dvi1,
+    /// Second DVI input.
+    Dvi2,
+    /// First composite video input.
+    Composite1,
+    /// Second composite video input.
+    Composite2,
+    /// First S-video input.
+    SVideo1,
+    /// Second S-video input.
+    SVideo2,
+    /// First tuner.
+    Tuner1,
+    /// Second tuner.
+    Tuner2,
+    /// Third tuner.
+    Tuner3,
+    /// First component video input.
+    Component1,
+    /// Second component video input.
+    Component2,
+    /// Third component video input.
+    Component3,
+    /// First DisplayPort input.
+    DisplayPort1,
+    /// Second DisplayPort input.
+    DisplayPort2,
+    /// First HDMI input.
+    Hdmi1,
+    /// Second HDMI input.
+    Hdmi2,
+    /// Input with a value not defined by MCCS, e.g. USB-C on some monitors.
+    Other(u8),
+}
+
+const INPUT_SOURCES: [(u8, InputSource); 18] = [
+    (0x01, InputSource::Vga1),
+    (0x02, InputSource::Vga2),
+    (0x03, InputSource::Dvi1),
+    (0x04, InputSource::Dvi2),
+    (0x05, InputSource::Composite1),
+    (0x06, InputSource::Composite2),
+    (0x07, InputSource::SVideo1),
+    (0x08, InputSource::SVideo2),
+    (0x09, InputSource::Tuner1),
+    (0x0a, InputSource::Tuner2),
+    (0x0b, InputSource::Tuner3),
+    (0x0c, InputSource::Component1),
+    (0x0d, InputSource::Component2),
+    (0x0e, InputSource::Component3),
+    (0x0f, InputSource::DisplayPort1),
+    (0x10, InputSource::DisplayPort2),
+    (0x11, InputSource::Hdmi1),
+    (0x12, InputSource::Hdmi2),
+];
+
+impl InputSource {
+    /// Returns the input for a VCP value.
+    pub fn from_value(value: u8) -> Self {
+        from_value(&INPUT_SOURCES, value).unwrap_or(InputSource::Other(value))
+    }
+
+    /// Returns the VCP value of the input.
+    pub fn value(self) -> u8 {
+        match self {
+            InputSource::Other(value) => value,
+            _ => to_value(&INPUT_SOURCES, self),
+        }
+    }
+}
+
+/// Power mode of a monitor (VCP code `0xD6`).
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum PowerMode {
+    /// Display on.
+    On,
+    /// Display in standby.
+    Standby,
+    /// Display suspended.
+    Suspend,
+    /// Display off, waking up on video signal.
+    Off,
+    /// Display off as if turned off with its power button.
+    PowerOff,
+    /// Mode with a value not defined by MCCS.
+    Other(u8),
+}
+
+const POWER_MODES: [(u8, PowerMode); 5] = [
+    (0x01, PowerMode::On),
+    (0x02, PowerMode::Standby),
+    (0x03, PowerMode::Suspend),
+    (0x04, PowerMode::Off),
+    (0x05, PowerMode::PowerOff),
+];
+
+impl PowerMode {
+    /// Returns the power mode for a VCP value.
+    pub fn from_value(value: u8) -> Self {
+        from_value(&POWER_MODES, value).unwrap_or(PowerMode::Other(value))
+    }
+
+    /// Returns the VCP value of the power mode.
+    pub fn value(self) -> u8 {
+        match self {
+            PowerMode::Other(value) => value,
+            _ => to_value(&POWER_MODES, self),
+        }
+    }
+}
+
+/// Color preset of a monitor (VCP code `0x14`).
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum ColorPreset {
+    /// sRGB.
+    Srgb,
+    /// Native colors of the display.
+    Native,
+    /// 4000 K color temperature.
+    Kelvin4000,
+    /// 5000 K color temperature.
+    Kelvin5000,
+    /// 6500 K color temperature.
+    Kelvin6500,
+    /// 7500 K color temperature.
+    Kelvin7500,
+    /// 8200 K color temperature.
+    Kelvin8200,
+    /// 9300 K color temperature.
+    Kelvin9300,
+    /// 10000 K color temperature.
+    Kelvin10000,
+    /// 11500 K color temperature.
+    Kelvin11500,
+    /// First user-defined preset.
+    User1,
+    /// Second user-defined preset.
+    User2,
+    /// Third user-defined preset.
+    User3,
+    /// Preset with a value not defined by MCCS.
+    Other(u8),
+}
+
+const COLOR_PRESETS: [(u8, ColorPreset); 13] = [
+    (0x01, ColorPreset::Srgb),
+    (0x02, ColorPreset::Native),
+    (0x03, ColorPreset::Kelvin4000),
+    (0x04, ColorPreset::Kelvin5000),
+    (0x05, ColorPreset::Kelvin6500),
+    (0x06, ColorPreset::Kelvin7500),
+    (0x07, ColorPreset::Kelvin8200),
+    (0x08, ColorPreset::Kelvin9300),
+    (0x09, ColorPreset::Kelvin10000),
+    (0x0a, ColorPreset::Kelvin11500),
+    (0x0b, ColorPreset::User1),
+    (0x0c, ColorPreset::User2),
+    (0x0d, ColorPreset::User3),
+];
+
+impl ColorPreset {
+    /// Returns the color preset for a VCP value.
+    pub fn from_value(value: u8) -> Self {
+        from_value(&COLOR_PRESETS, value).unwrap_or(ColorPreset::Other(value))
+    }
+
+    /// Returns the VCP value of the color preset.
+    pub fn value(self) -> u8 {
+        match self {
+            ColorPreset::Other(value) => value,
+            _ => to_value(&COLOR_PRESETS, self),
+        }
+    }
+}
+
+fn from_value<T: Copy>(table: &[(u8, T)], value: u8) -> Option<T> {
+    table.iter().find(|(v, _)| *v == value).map(|&(_, t)| t)
+}
+
+fn to_value<T: PartialEq>(table: &[(u8, T)], t: T) -> u8 {
+    table.iter().find(|(_, x)| *x == t).map_or(0, |&(v, _)| v)
+}
@@ -0,0 +1,47 @@
+// Copyright (C) 2022 The brightness project authors. Distributed under the 0BSD license.
+
+//! Waiting for brightness changes.
+
+use crate::Error;
+use std::time::Duration;
+
+/// Interval at which brightness is read again when changes are not notified.
+pub(crate) const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Blocks until the brightness of a device or the set of devices may have changed.
+#[derive(Debug)]
+pub(crate) enum Watcher {
+    #[cfg(target_os = "linux")]
+    Attribute(crate::blocking::linux::AttributeWatcher),
+    #[cfg(target_os = "linux")]
+    Directory(crate::blocking::linux::DirectoryWatcher),
+    #[cfg(feature = "mock")]
+    Mock(crate::mock::MockWatcher),
+    #[cfg(not(target_os = "linux"))]
+    Interval,
+}
+
+impl Watcher {
+    /// Waits for a change notification, or at most [`POLL_INTERVAL`].
+    pub(crate) fn wait(&mut self) -> Result<(), Error> {
+        match self {
+            #[cfg(target_os = "linux")]
+            Watcher::Attribute(w) => Ok(w.wait(POLL_INTERVAL)?),
+            #[cfg(target_os = "linux")]
+            Watcher::Directory(w) => {
+                w.wait(POLL_INTERVAL);
+                Ok(())
+            }
+            #[cfg(feature = "mock")]
+            Watcher::Mock(w) => {
+                w.wait(POLL_INTERVAL);
+                Ok(())
+            }
+            #[cfg(not(target_os = "linux"))]
+            Watcher::Interval => {
+                std::thread::sleep(POLL_INTERVAL);
+                Ok(())
+            }
+        }
+    }
+}
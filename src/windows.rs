@@ -5,11 +5,14 @@
 pub use crate::blocking::windows::BrightnessExt;
 
 use crate::{
-    BrightnessDevice, Error,
+    BrightnessDevice, DeviceType, Error, Scale, VcpValue,
+    r#async::DeviceImpl,
+    backlight::Parent,
     blocking::{
         Brightness,
         windows::{BlockingDeviceImpl, SysError},
     },
+    watch::Watcher,
 };
 use blocking::unblock;
 use futures::{FutureExt, Stream, StreamExt, stream};
@@ -18,6 +21,63 @@ use std::sync::Arc;
 #[derive(Debug)]
 pub(crate) struct AsyncDeviceImpl(Arc<BlockingDeviceImpl>);
 
+impl AsyncDeviceImpl {
+    pub(crate) fn from_blocking(device: BlockingDeviceImpl) -> Self {
+        Self(Arc::new(device))
+    }
+
+    pub(crate) fn scale(&self) -> Scale {
+        self.0.scale()
+    }
+
+    pub(crate) fn device_type(&self) -> DeviceType {
+        self.0.device_type()
+    }
+
+    pub(crate) fn parent(&self) -> Option<Parent> {
+        self.0.parent()
+    }
+
+    pub(crate) fn watcher(&self) -> Result<Watcher, SysError> {
+        self.0.watcher()
+    }
+
+    pub(crate) fn connector_name(&self) -> Option<String> {
+        self.0.connector_name()
+    }
+
+    pub(crate) fn is_internal(&self) -> bool {
+        self.0.is_internal()
+    }
+
+    pub(crate) fn edid(&self) -> Result<Option<Vec<u8>>, SysError> {
+        self.0.edid()
+    }
+
+    pub(crate) fn driver(&self) -> String {
+        self.0.driver()
+    }
+
+    pub(crate) fn stable_path(&self) -> String {
+        self.0.stable_path()
+    }
+
+    pub(crate) async fn get_vcp(&self, code: u8) -> Result<VcpValue, SysError> {
+        let cloned = Arc::clone(&self.0);
+        unblock(move || cloned.get_vcp(code)).await
+    }
+
+    pub(crate) async fn set_vcp(&self, code: u8, value: u16) -> Result<(), SysError> {
+        let cloned = Arc::clone(&self.0);
+        unblock(move || cloned.set_vcp(code, value)).await
+    }
+
+    pub(crate) async fn capabilities_string(&self) -> Result<String, SysError> {
+        let cloned = Arc::clone(&self.0);
+        unblock(move || cloned.capabilities_string()).await
+    }
+}
+
 // Windows doesn't have an async C API for monitors, so we will instead spawn the blocking tasks on
 // background threads.
 impl crate::Brightness for AsyncDeviceImpl {
@@ -34,6 +94,21 @@ impl crate::Brightness for AsyncDeviceImpl {
         let cloned = Arc::clone(&self.0);
         unblock(move || cloned.set(percentage)).await
     }
+
+    async fn get_raw(&self) -> Result<u32, Error> {
+        let cloned = Arc::clone(&self.0);
+        unblock(move || cloned.get_raw()).await
+    }
+
+    async fn max_raw(&self) -> Result<u32, Error> {
+        let cloned = Arc::clone(&self.0);
+        unblock(move || cloned.max_raw()).await
+    }
+
+    async fn set_raw(&mut self, value: u32) -> Result<(), Error> {
+        let cloned = Arc::clone(&self.0);
+        unblock(move || cloned.set_raw(value)).await
+    }
 }
 
 pub(crate) fn brightness_devices() -> impl Stream<Item = Result<AsyncDeviceImpl, SysError>> {
@@ -46,14 +121,26 @@ pub(crate) fn brightness_devices() -> impl Stream<Item = Result<AsyncDeviceImpl,
 
 impl BrightnessExt for BrightnessDevice {
     fn device_description(&self) -> Result<String, Error> {
-        Ok(self.0.0.device_description.clone())
+        match &self.inner {
+            DeviceImpl::Platform(d) => Ok(d.0.device_description.clone()),
+            #[cfg(feature = "mock")]
+            DeviceImpl::Mock(d) => Err(d.unsupported()),
+        }
     }
 
     fn device_registry_key(&self) -> Result<String, Error> {
-        Ok(self.0.0.device_key.clone())
+        match &self.inner {
+            DeviceImpl::Platform(d) => Ok(d.0.device_key.clone()),
+            #[cfg(feature = "mock")]
+            DeviceImpl::Mock(d) => Err(d.unsupported()),
+        }
     }
 
     fn device_path(&self) -> Result<String, Error> {
-        Ok(self.0.0.device_path.clone())
+        match &self.inner {
+            DeviceImpl::Platform(d) => Ok(d.0.device_path.clone()),
+            #[cfg(feature = "mock")]
+            DeviceImpl::Mock(d) => Err(d.unsupported()),
+        }
     }
 }